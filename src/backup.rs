@@ -0,0 +1,225 @@
+use anyhow::{Context, Result};
+use chrono::{DateTime, Local, NaiveDateTime, TimeZone, Utc};
+use std::{
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use crate::state::{self, StateData};
+
+const MAX_BACKUPS: usize = 20;
+/// minimum age of the newest backup before another one is taken
+const BACKUP_INTERVAL_MINUTES: i64 = 10;
+/// utc, followed by `Z` in file names, older backups were named in local time without it
+const TIMESTAMP_FORMAT: &str = "%Y%m%d-%H%M%S";
+
+#[derive(Debug, Clone)]
+pub struct Backup {
+    pub path: PathBuf,
+    pub timestamp: DateTime<Utc>,
+    pub assigned: usize,
+}
+
+/// writes into a temp file first so a crash never leaves a half written file behind
+pub fn write_atomic(path: &Path, content: &[u8]) -> Result<()> {
+    write_atomic_with(path, |f| Ok(f.write_all(content)?))
+}
+
+/// like `write_atomic` for content written piece by piece, the temp file is removed on errors
+pub fn write_atomic_with(path: &Path, write: impl FnOnce(&mut File) -> Result<()>) -> Result<()> {
+    let tmp = path.with_extension("tmp");
+    let res = File::create(&tmp)
+        .map_err(anyhow::Error::from)
+        .and_then(|mut f| {
+            write(&mut f)?;
+            Ok(f.sync_all()?)
+        });
+    if let Err(e) = res {
+        std::fs::remove_file(&tmp).ok();
+        return Err(e);
+    }
+
+    std::fs::rename(&tmp, path)
+        .with_context(|| format!("rename '{}' -> '{}'", tmp.display(), path.display()))?;
+    Ok(())
+}
+
+/// `force` ignores the backup interval
+pub fn create(dir: &Path, content: &str, force: bool) -> Result<()> {
+    // utc so the interval does not jump when daylight saving time ends
+    let now = Utc::now();
+
+    if !force {
+        if let Some(newest) = list_files(dir).last() {
+            if (now - newest.1).num_minutes() < BACKUP_INTERVAL_MINUTES {
+                return Ok(());
+            }
+        }
+    }
+
+    std::fs::create_dir_all(dir)?;
+    // backups forced within the same second get a sequence number
+    let stamp = now.format(TIMESTAMP_FORMAT);
+    let path = (0..)
+        .map(|seq| match seq {
+            0 => dir.join(format!("state-{stamp}Z.ron")),
+            seq => dir.join(format!("state-{stamp}Z-{seq}.ron")),
+        })
+        .find(|path| !path.exists())
+        .expect("some sequence number is free");
+    tracing::info!("create backup: {:?}", path);
+    write_atomic(&path, content.as_bytes())?;
+
+    let files = list_files(dir);
+    for (path, _) in files.iter().take(files.len().saturating_sub(MAX_BACKUPS)) {
+        tracing::debug!("remove backup: {:?}", path);
+        std::fs::remove_file(path)?;
+    }
+
+    Ok(())
+}
+
+/// newest first
+pub fn list(dir: &Path) -> Vec<Backup> {
+    list_files(dir)
+        .into_iter()
+        .rev()
+        .map(|(path, timestamp)| {
+            let assigned = read(&path).map(|s| assigned_count(&s)).unwrap_or_default();
+            Backup {
+                path,
+                timestamp,
+                assigned,
+            }
+        })
+        .collect()
+}
+
+pub fn read(path: &Path) -> Result<StateData> {
+    state::from_str(&std::fs::read_to_string(path)?)
+}
+
+fn assigned_count(state: &StateData) -> usize {
+    state
+        .rows
+        .values()
+        .chain(state.row_meta_data.iter())
+        .filter(|m| m.has_receipts())
+        .count()
+}
+
+/// oldest first
+fn list_files(dir: &Path) -> Vec<(PathBuf, DateTime<Utc>)> {
+    let Ok(entries) = std::fs::read_dir(dir) else {
+        return Vec::new();
+    };
+
+    let mut res: Vec<_> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter_map(|path| {
+            let stem = path.file_stem()?.to_str()?.strip_prefix("state-")?;
+            let (timestamp, seq) = parse_stem(stem)?;
+            Some((path, timestamp, seq))
+        })
+        .collect();
+
+    res.sort_by_key(|(_, timestamp, seq)| (*timestamp, *seq));
+    res.into_iter()
+        .map(|(path, timestamp, _)| (path, timestamp))
+        .collect()
+}
+
+/// `20231029-023000Z-1` or the local time of older backups, `20231029-023000`
+fn parse_stem(stem: &str) -> Option<(DateTime<Utc>, u32)> {
+    let Some((timestamp, seq)) = stem.split_once('Z') else {
+        let local = NaiveDateTime::parse_from_str(stem, TIMESTAMP_FORMAT).ok()?;
+        // a time skipped by daylight saving time cannot be mapped, it is taken as utc then
+        let timestamp = match Local.from_local_datetime(&local).earliest() {
+            Some(timestamp) => timestamp.with_timezone(&Utc),
+            None => Utc.from_utc_datetime(&local),
+        };
+        return Some((timestamp, 0));
+    };
+
+    let timestamp = NaiveDateTime::parse_from_str(timestamp, TIMESTAMP_FORMAT).ok()?;
+    let seq = match seq.strip_prefix('-') {
+        Some(seq) => seq.parse().ok()?,
+        None if seq.is_empty() => 0,
+        None => return None,
+    };
+    Some((Utc.from_utc_datetime(&timestamp), seq))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdir::TestDir;
+
+    fn content() -> String {
+        ron::to_string(&StateData::default()).unwrap()
+    }
+
+    #[test]
+    fn interval() {
+        let dir = TestDir::new("backup");
+        create(&dir, &content(), false).unwrap();
+        create(&dir, &content(), false).unwrap();
+        assert_eq!(list(&dir).len(), 1);
+
+        // forced backups in the same second do not overwrite each other
+        create(&dir, &content(), true).unwrap();
+        create(&dir, &content(), true).unwrap();
+        let backups = list(&dir);
+        assert_eq!(backups.len(), 3);
+        assert!(backups.windows(2).all(|b| b[0].timestamp >= b[1].timestamp));
+    }
+
+    #[test]
+    fn oldest_are_removed() {
+        let dir = TestDir::new("backup");
+        std::fs::write(dir.join("state-20200101-120000Z.ron"), content()).unwrap();
+        for _ in 0..MAX_BACKUPS {
+            create(&dir, &content(), true).unwrap();
+        }
+
+        let backups = list(&dir);
+        assert_eq!(backups.len(), MAX_BACKUPS);
+        assert!(!dir.join("state-20200101-120000Z.ron").exists());
+        // sequence numbers order backups of the same second
+        let order: Vec<_> = backups
+            .iter()
+            .map(|b| {
+                let stem = b.path.file_stem().unwrap().to_str().unwrap();
+                parse_stem(stem.strip_prefix("state-").unwrap()).unwrap()
+            })
+            .collect();
+        assert!(order.windows(2).all(|o| o[0] > o[1]));
+    }
+
+    #[test]
+    fn file_names() {
+        let utc = |s| {
+            Utc.from_utc_datetime(&NaiveDateTime::parse_from_str(s, "%Y-%m-%d %H:%M:%S").unwrap())
+        };
+        assert_eq!(
+            parse_stem("20231029-013000Z"),
+            Some((utc("2023-10-29 01:30:00"), 0))
+        );
+        assert_eq!(
+            parse_stem("20231029-013000Z-12"),
+            Some((utc("2023-10-29 01:30:00"), 12))
+        );
+        assert_eq!(parse_stem("20231029-013000Zx"), None);
+        assert_eq!(parse_stem("20231029-013000Z-"), None);
+        assert_eq!(parse_stem("latest"), None);
+
+        // older backups in local time
+        let local = Local
+            .with_ymd_and_hms(2023, 6, 1, 12, 0, 0)
+            .unwrap()
+            .with_timezone(&Utc);
+        assert_eq!(parse_stem("20230601-120000"), Some((local, 0)));
+    }
+}
@@ -0,0 +1,265 @@
+use anyhow::{bail, Result};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::{
+    export::PackageRow,
+    pdf::{PdfWriter, TextLine},
+    receipttype::ReceiptKind,
+};
+
+const TITLE_SIZE: f32 = 16.0;
+const TEXT_SIZE: f32 = 11.0;
+const LIST_SIZE: f32 = 8.0;
+const DESCRIPTION_CHARS: usize = 40;
+
+/// writes one pdf: a cover listing all rows, then every row with receipts as a
+/// separator page followed by its receipts, rows needing no receipt get a page with the reason,
+/// returns the receipts that could not be included
+pub fn write_bundle(target: &Path, title: &str, rows: &[PackageRow]) -> Result<Vec<PathBuf>> {
+    let mut writer = PdfWriter::new();
+    writer.add_text(&cover(title, rows))?;
+
+    // shared receipts are only included with their first row
+    let mut included = HashMap::<&Path, usize>::new();
+    let mut skipped = Vec::new();
+
+    for row in rows.iter().filter(|r| !r.hidden) {
+        if row.receipts.is_empty() {
+            if let Some(reason) = &row.no_receipt {
+                let mut lines = row_heading(row);
+                lines.push(TextLine::new("", TEXT_SIZE));
+                lines.push(TextLine::new("No receipt needed:", TEXT_SIZE));
+                lines.push(TextLine::new(reason.as_str(), TEXT_SIZE));
+                writer.add_text(&lines)?;
+            }
+            continue;
+        }
+
+        let mut lines = row_heading(row);
+        lines.push(TextLine::new("", TEXT_SIZE));
+        lines.push(TextLine::new("Receipts:", TEXT_SIZE));
+        for (source, name) in &row.receipts {
+            let line = match included.get(source.as_path()) {
+                Some(owner) => format!("  {name} (included with row {owner:0>3})"),
+                None => format!("  {name}"),
+            };
+            lines.push(TextLine::new(line, TEXT_SIZE));
+        }
+        writer.add_text(&lines)?;
+
+        for (source, name) in &row.receipts {
+            if included.contains_key(source.as_path()) {
+                continue;
+            }
+            included.insert(source, row.row);
+
+            // one broken receipt should not spoil the whole bundle
+            if let Err(e) = add_receipt(&mut writer, source, name) {
+                tracing::warn!("receipt not included: {:?}: {:#}", source, e);
+                skipped.push(source.clone());
+                writer.add_text(&[
+                    TextLine::new(format!("Receipt {name}"), TITLE_SIZE),
+                    TextLine::new("", TEXT_SIZE),
+                    TextLine::new(format!("could not be included: {e:#}"), TEXT_SIZE),
+                ])?;
+            }
+        }
+    }
+
+    let pages = writer.page_count();
+    writer.save(target)?;
+
+    tracing::info!("exported bundle: {:?} ({} pages)", target, pages);
+
+    Ok(skipped)
+}
+
+fn add_receipt(writer: &mut PdfWriter, source: &Path, name: &str) -> Result<()> {
+    let kind = match ReceiptKind::from_path(source) {
+        Some(ReceiptKind::Pdf) => return writer.add_pdf(source),
+        Some(ReceiptKind::Image) => return writer.add_image(source),
+        Some(kind) => kind,
+        None => bail!("unknown receipt type"),
+    };
+
+    // mails and documents cannot be embedded, their text stands in for them
+    let text = kind.extract_text(source)?;
+    let mut lines = vec![
+        TextLine::new(format!("Receipt {name}"), TITLE_SIZE),
+        TextLine::new("text of the original file:", TEXT_SIZE),
+        TextLine::new("", TEXT_SIZE),
+    ];
+    let text = TextLine::wrapped(&text, TEXT_SIZE);
+    if text.is_empty() {
+        lines.push(TextLine::new("(no text found)", TEXT_SIZE));
+    }
+    lines.extend(text);
+
+    writer.add_text(&lines)
+}
+
+fn cover(title: &str, rows: &[PackageRow]) -> Vec<TextLine> {
+    let count = |f: fn(&PackageRow) -> bool| rows.iter().filter(|r| f(r)).count();
+    let assigned = count(|r| !r.hidden && !r.receipts.is_empty());
+    let missing = count(|r| !r.hidden && r.receipts.is_empty() && r.no_receipt.is_none());
+
+    let mut lines = vec![
+        TextLine::new(title, TITLE_SIZE),
+        TextLine::new(
+            format!(
+                "{} rows, {} with receipts, {} missing receipts",
+                rows.len(),
+                assigned,
+                missing
+            ),
+            TEXT_SIZE,
+        ),
+        TextLine::new("", TEXT_SIZE),
+        TextLine::new(
+            format!(
+                "{:<3}  {:<10}  {:>14}  {:<DESCRIPTION_CHARS$}  status",
+                "row", "date", "amount", "description"
+            ),
+            LIST_SIZE,
+        ),
+    ];
+
+    for row in rows {
+        let t = row.transaction.as_ref();
+        let date = t
+            .map(|t| t.date.format("%Y-%m-%d").to_string())
+            .unwrap_or_default();
+        let amount = t
+            .map(|t| format!("{} {}", t.amount, t.currency))
+            .unwrap_or_default();
+        let description: String = t
+            .map(|t| t.description.chars().take(DESCRIPTION_CHARS).collect())
+            .unwrap_or_default();
+
+        lines.push(TextLine::new(
+            format!(
+                "{:0>3}  {:<10}  {:>14}  {:<DESCRIPTION_CHARS$}  {}",
+                row.row,
+                date,
+                amount,
+                description,
+                status(row)
+            ),
+            LIST_SIZE,
+        ));
+    }
+
+    lines
+}
+
+fn status(row: &PackageRow) -> String {
+    match (row.receipts.len(), &row.no_receipt) {
+        _ if row.hidden => "hidden".to_string(),
+        (0, Some(reason)) => format!("no receipt: {reason}"),
+        (0, None) => "missing".to_string(),
+        (1, _) => "1 receipt".to_string(),
+        (n, _) => format!("{n} receipts"),
+    }
+}
+
+fn row_heading(row: &PackageRow) -> Vec<TextLine> {
+    let mut lines = vec![TextLine::new(format!("Row {:0>3}", row.row), TITLE_SIZE)];
+    if let Some(t) = &row.transaction {
+        lines.extend([
+            TextLine::new(t.date.format("%Y-%m-%d").to_string(), TEXT_SIZE),
+            TextLine::new(t.description.as_str(), TEXT_SIZE),
+            TextLine::new(format!("{} {}", t.amount, t.currency), TEXT_SIZE),
+        ]);
+    }
+    if let Some(category) = &row.category {
+        lines.push(TextLine::new(format!("category: {category}"), TEXT_SIZE));
+    }
+    lines
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdir::TestDir;
+
+    fn row(row: usize, receipts: &[&Path], no_receipt: Option<&str>) -> PackageRow {
+        PackageRow {
+            row,
+            transaction: None,
+            hidden: false,
+            category: None,
+            no_receipt: no_receipt.map(str::to_string),
+            receipts: receipts
+                .iter()
+                .map(|p| {
+                    (
+                        p.to_path_buf(),
+                        p.file_name().unwrap().to_string_lossy().to_string(),
+                    )
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn bundle_pages() {
+        let dir = TestDir::new("bundle");
+
+        let pdf = dir.join("invoice.pdf");
+        let mut writer = PdfWriter::new();
+        writer
+            .add_text(&[TextLine::new("page 1", TEXT_SIZE)])
+            .unwrap();
+        writer
+            .add_text(&[TextLine::new("page 2", TEXT_SIZE)])
+            .unwrap();
+        writer.save(&pdf).unwrap();
+
+        let image = dir.join("scan.png");
+        image::RgbImage::new(4, 3).save(&image).unwrap();
+
+        let html = dir.join("order.html");
+        std::fs::write(
+            &html,
+            "<html><body><p>Order 4711</p><p>12,34 EUR</p></body></html>",
+        )
+        .unwrap();
+
+        let rows = vec![
+            row(0, &[&pdf], None),
+            row(1, &[&image], None),
+            // shares the pdf of row 0
+            row(2, &[&pdf], None),
+            row(3, &[], Some("bank fee")),
+            row(4, &[&html], None),
+            // missing receipts are only listed on the cover
+            row(5, &[], None),
+        ];
+
+        let target = dir.join("bundle.pdf");
+        let skipped = write_bundle(&target, "Statement", &rows).unwrap();
+        assert!(skipped.is_empty());
+
+        // cover, 2 pdf pages, image, html text and a page for each of rows 0 to 4
+        let doc = lopdf::Document::load(&target).unwrap();
+        assert_eq!(doc.get_pages().len(), 1 + 2 + 1 + 1 + 5);
+    }
+
+    #[test]
+    fn broken_receipt_is_skipped() {
+        let dir = TestDir::new("bundle");
+        let broken = dir.join("broken.pdf");
+        std::fs::write(&broken, "not a pdf").unwrap();
+
+        let target = dir.join("bundle.pdf");
+        let skipped = write_bundle(&target, "Statement", &[row(0, &[&broken], None)]).unwrap();
+        assert_eq!(skipped, vec![broken]);
+
+        // cover, separator and the page telling the receipt is missing
+        let doc = lopdf::Document::load(&target).unwrap();
+        assert_eq!(doc.get_pages().len(), 3);
+    }
+}
@@ -0,0 +1,145 @@
+use anyhow::Result;
+use globset::{Glob, GlobSet, GlobSetBuilder};
+use std::path::{Path, PathBuf};
+use walkdir::WalkDir;
+
+use crate::receipttype::ReceiptKind;
+
+/// folder the app keeps its own files in, never searched for receipts
+const PROJECT_DIR: &str = ".ccaccounting";
+
+#[derive(Debug, Clone, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub struct ReceiptFolder {
+    /// relative paths are resolved against the folder of the csv
+    pub path: PathBuf,
+    #[serde(default)]
+    pub recursive: bool,
+}
+
+#[derive(Debug, Default)]
+pub struct Discovery {
+    pub receipts: Vec<PathBuf>,
+    /// entries that could not be read
+    pub errors: Vec<String>,
+}
+
+/// patterns without a `/` match file or folder names anywhere, others match
+/// the path relative to the receipt folder
+pub fn ignore_set(patterns: &[String]) -> Result<GlobSet> {
+    let mut builder = GlobSetBuilder::new();
+    for pattern in patterns.iter().map(|p| p.trim()).filter(|p| !p.is_empty()) {
+        builder.add(Glob::new(pattern)?);
+    }
+    Ok(builder.build()?)
+}
+
+/// where receipts are searched and which files count as receipts
+#[derive(Debug, Default)]
+pub struct ReceiptSources {
+    /// folders with resolved paths
+    folders: Vec<ReceiptFolder>,
+    kinds: Vec<ReceiptKind>,
+    ignore: GlobSet,
+}
+
+impl ReceiptSources {
+    pub fn new(
+        base: &Path,
+        folders: &[ReceiptFolder],
+        kinds: Vec<ReceiptKind>,
+        ignore_patterns: &[String],
+    ) -> Result<Self> {
+        Ok(Self {
+            folders: folders
+                .iter()
+                .map(|f| ReceiptFolder {
+                    path: base.join(&f.path),
+                    recursive: f.recursive,
+                })
+                .collect(),
+            kinds,
+            ignore: ignore_set(ignore_patterns)?,
+        })
+    }
+
+    pub fn folders(&self) -> &[ReceiptFolder] {
+        &self.folders
+    }
+
+    fn is_ignored(&self, name: &Path, relative: &Path) -> bool {
+        name == Path::new(PROJECT_DIR)
+            || self.ignore.is_match(relative)
+            || self.ignore.is_match(name)
+    }
+
+    fn is_receipt_kind(&self, path: &Path) -> bool {
+        ReceiptKind::from_path(path).is_some_and(|kind| self.kinds.contains(&kind))
+    }
+
+    pub fn find(&self) -> Discovery {
+        let mut res = Discovery::default();
+
+        for folder in &self.folders {
+            let walk = WalkDir::new(&folder.path)
+                .min_depth(1)
+                .max_depth(if folder.recursive { usize::MAX } else { 1 })
+                .sort_by_file_name();
+
+            let entries = walk.into_iter().filter_entry(|entry| {
+                let relative = entry
+                    .path()
+                    .strip_prefix(&folder.path)
+                    .unwrap_or(entry.path());
+                !self.is_ignored(Path::new(entry.file_name()), relative)
+            });
+
+            for entry in entries {
+                match entry {
+                    Ok(entry) => {
+                        let path = entry.path();
+                        if path.is_file()
+                            && self.is_receipt_kind(path)
+                            && !res.receipts.iter().any(|p| p == path)
+                        {
+                            res.receipts.push(path.to_path_buf());
+                        }
+                    }
+                    Err(e) => {
+                        tracing::warn!("unreadable entry: {}", e);
+                        res.errors.push(e.to_string());
+                    }
+                }
+            }
+        }
+
+        res
+    }
+
+    /// true if `find` would list this path
+    pub fn accepts(&self, path: &Path) -> bool {
+        path.is_file() && self.is_receipt_kind(path) && self.is_searched(path)
+    }
+
+    /// true for folders `find` would descend into
+    pub fn accepts_folder(&self, path: &Path) -> bool {
+        path.is_dir() && self.is_searched(path)
+    }
+
+    fn is_searched(&self, path: &Path) -> bool {
+        self.folders.iter().any(|folder| {
+            let Ok(relative) = path.strip_prefix(&folder.path) else {
+                return false;
+            };
+            if !folder.recursive && relative.components().count() != 1 {
+                return false;
+            }
+
+            // every folder on the way must not be ignored either
+            let mut prefix = PathBuf::new();
+            relative.components().all(|component| {
+                prefix.push(component);
+                !self.is_ignored(Path::new(component.as_os_str()), &prefix)
+            })
+        })
+    }
+}
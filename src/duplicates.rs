@@ -0,0 +1,185 @@
+use anyhow::Result;
+use sha2::{Digest, Sha256};
+use std::{
+    collections::HashMap,
+    fs::File,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+    time::SystemTime,
+};
+
+pub fn hash_file(path: &Path) -> Result<String> {
+    let mut f = File::open(path)?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut f, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+pub fn modified(path: &Path) -> Option<SystemTime> {
+    std::fs::metadata(path).and_then(|m| m.modified()).ok()
+}
+
+/// hash of a file with the modification time it was computed for,
+/// `None` for unreadable files so they are only retried once they change
+type Entry = (Option<SystemTime>, Option<String>);
+
+/// content hashes of receipts, computed in the background
+#[derive(Debug, Default)]
+pub struct Hashes {
+    hashes: HashMap<PathBuf, Entry>,
+    pending: Option<Receiver<(PathBuf, Entry)>>,
+}
+
+impl Hashes {
+    /// starts hashing all files not hashed yet or changed since
+    pub fn scan(&mut self, paths: &[PathBuf]) {
+        let todo: Vec<PathBuf> = paths
+            .iter()
+            .filter(|p| {
+                self.hashes
+                    .get(*p)
+                    .map(|(time, _)| *time != modified(p))
+                    .unwrap_or(true)
+            })
+            .cloned()
+            .collect();
+
+        if todo.is_empty() || self.pending.is_some() {
+            return;
+        }
+
+        let (tx, rx) = channel();
+        std::thread::spawn(move || {
+            for path in todo {
+                let time = modified(&path);
+                let hash = hash_file(&path)
+                    .map_err(|e| tracing::warn!("hashing {:?} failed: {}", path, e))
+                    .ok();
+                if tx.send((path, (time, hash))).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.pending = Some(rx);
+    }
+
+    /// returns true if new hashes arrived or the scan is done
+    pub fn poll(&mut self) -> bool {
+        let Some(rx) = self.pending.as_ref() else {
+            return false;
+        };
+
+        let mut received = false;
+        loop {
+            match rx.try_recv() {
+                Ok((path, entry)) => {
+                    self.hashes.insert(path, entry);
+                    received = true;
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.pending = None;
+                    received = true;
+                    break;
+                }
+            }
+        }
+
+        received
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&str> {
+        self.hashes.get(path).and_then(|(_, hash)| hash.as_deref())
+    }
+
+    /// indices into `paths` of files with identical content, in the order of `paths`
+    pub fn groups(&self, paths: &[PathBuf]) -> Vec<Vec<usize>> {
+        let mut groups: Vec<Vec<usize>> = Vec::new();
+        let mut by_hash = HashMap::<&str, usize>::new();
+        for (idx, path) in paths.iter().enumerate() {
+            let Some(hash) = self.get(path) else {
+                continue;
+            };
+            match by_hash.get(hash) {
+                Some(group) => groups[*group].push(idx),
+                None => {
+                    by_hash.insert(hash, groups.len());
+                    groups.push(vec![idx]);
+                }
+            }
+        }
+
+        groups.retain(|group| group.len() > 1);
+        groups
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdir::TestDir;
+
+    /// a directory with files of the given contents
+    fn files(contents: &[&str]) -> (TestDir, Vec<PathBuf>) {
+        let dir = TestDir::new("hashes");
+        let paths = contents
+            .iter()
+            .enumerate()
+            .map(|(idx, content)| {
+                let path = dir.join(format!("{idx}.pdf"));
+                std::fs::write(&path, content).unwrap();
+                path
+            })
+            .collect();
+        (dir, paths)
+    }
+
+    /// hashes all `paths` and waits for the scan to finish
+    fn hash(hashes: &mut Hashes, paths: &[PathBuf]) {
+        hashes.scan(paths);
+        while hashes.is_busy() {
+            hashes.poll();
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn hash_files() {
+        let (dir, mut paths) = files(&["same", "same", "other"]);
+        paths.push(dir.join("gone.pdf"));
+
+        let mut hashes = Hashes::default();
+        hash(&mut hashes, &paths);
+        assert_eq!(
+            hashes.get(&paths[0]),
+            Some(hash_file(&paths[0]).unwrap().as_str())
+        );
+        assert_eq!(hashes.get(&paths[0]), hashes.get(&paths[1]));
+        assert_ne!(hashes.get(&paths[0]), hashes.get(&paths[2]));
+
+        // unreadable files are remembered without a hash and not scanned again
+        assert_eq!(hashes.get(&paths[3]), None);
+        hashes.scan(&paths);
+        assert!(!hashes.is_busy());
+    }
+
+    #[test]
+    fn groups() {
+        let (dir, mut paths) = files(&["x", "y", "x", "z", "y", "x"]);
+        paths.push(dir.join("gone.pdf"));
+
+        let mut hashes = Hashes::default();
+        assert!(hashes.groups(&paths).is_empty());
+
+        hash(&mut hashes, &paths);
+        assert_eq!(hashes.groups(&paths), vec![vec![0, 2, 5], vec![1, 4]]);
+        // indices are into the paths given, single files form no group
+        assert_eq!(hashes.groups(&paths[2..]), vec![vec![0, 3]]);
+        assert!(hashes.groups(&paths[3..5]).is_empty());
+    }
+}
@@ -0,0 +1,413 @@
+use anyhow::{Context, Result};
+use rfd::FileDialog;
+use std::{
+    collections::{HashMap, HashSet},
+    fs::File,
+    io::Write,
+    path::{Path, PathBuf},
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{channel, Receiver, TryRecvError},
+    },
+};
+use zip::{write::FileOptions, CompressionMethod, ZipWriter};
+
+use crate::{backup, bundle, pdf, receipttype::ReceiptKind, transaction::Transaction};
+
+const RECEIPTS_DIR: &str = "receipts";
+
+/// a row of the statement as it goes into the package
+#[derive(Debug, Clone)]
+pub struct PackageRow {
+    pub row: usize,
+    pub transaction: Option<Transaction>,
+    pub hidden: bool,
+    pub category: Option<String>,
+    /// reason the row needs no receipt
+    pub no_receipt: Option<String>,
+    /// receipt and its file name in the package
+    pub receipts: Vec<(PathBuf, String)>,
+}
+
+/// a finished export
+#[derive(Debug)]
+pub struct Exported {
+    pub path: PathBuf,
+    /// receipts left out, e.g. because they were moved or deleted
+    pub skipped: Vec<PathBuf>,
+}
+
+#[derive(Debug, serde::Serialize)]
+struct IndexEntry {
+    row: usize,
+    date: String,
+    amount: String,
+    currency: String,
+    description: String,
+    category: String,
+    status: &'static str,
+    files: Vec<String>,
+    /// assigned receipts that were not found
+    missing_files: Vec<String>,
+}
+
+impl IndexEntry {
+    fn new(row: &PackageRow, files: Vec<String>, missing_files: Vec<String>) -> Self {
+        let status = if row.hidden {
+            "hidden"
+        } else if !missing_files.is_empty() {
+            "file missing"
+        } else if files.is_empty() && row.no_receipt.is_some() {
+            "no receipt needed"
+        } else if files.is_empty() {
+            "missing"
+        } else {
+            "assigned"
+        };
+        let t = row.transaction.as_ref();
+
+        Self {
+            row: row.row,
+            date: t
+                .map(|t| t.date.format("%Y-%m-%d").to_string())
+                .unwrap_or_default(),
+            amount: t.map(|t| t.amount.to_string()).unwrap_or_default(),
+            currency: t.map(|t| t.currency.clone()).unwrap_or_default(),
+            description: t.map(|t| t.description.clone()).unwrap_or_default(),
+            category: row.category.clone().unwrap_or_default(),
+            status,
+            files,
+            missing_files,
+        }
+    }
+}
+
+/// writes a zip with the receipts under their target names, an index and the rows
+/// still missing receipts, the receipts themselves are left untouched,
+/// returns the assigned receipts that were not found
+pub fn write_package(target: &Path, rows: &[PackageRow]) -> Result<Vec<PathBuf>> {
+    let mut skipped = Vec::new();
+    backup::write_atomic_with(target, |file| {
+        skipped = write_zip(file, rows)?;
+        Ok(())
+    })?;
+
+    tracing::info!(
+        "exported package: {:?} ({} receipts missing)",
+        target,
+        skipped.len()
+    );
+
+    Ok(skipped)
+}
+
+fn write_zip(file: &mut File, rows: &[PackageRow]) -> Result<Vec<PathBuf>> {
+    let mut zip = ZipWriter::new(file);
+    let options = FileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    // shared receipts go into the package once
+    let mut added = HashMap::<&Path, String>::new();
+    let mut names = HashSet::<String>::new();
+    let mut skipped = Vec::new();
+    let mut index = Vec::new();
+
+    for row in rows {
+        let mut files = Vec::new();
+        let mut missing_files = Vec::new();
+        for (source, name) in &row.receipts {
+            if let Some(name) = added.get(source.as_path()) {
+                files.push(name.clone());
+                continue;
+            }
+
+            // receipts moved or deleted outside of the app are listed instead
+            if !source.exists() {
+                missing_files.push(source.to_string_lossy().to_string());
+                if !skipped.contains(source) {
+                    skipped.push(source.clone());
+                }
+                continue;
+            }
+
+            let name = unique_name(name, &names);
+            let content = receipt_content(source, &name)?;
+
+            let path = format!("{RECEIPTS_DIR}/{name}");
+            zip.start_file(path.as_str(), options)?;
+            zip.write_all(&content)?;
+
+            names.insert(name.clone());
+            added.insert(source, path.clone());
+            files.push(path);
+        }
+
+        index.push(IndexEntry::new(row, files, missing_files));
+    }
+
+    zip.start_file("index.json", options)?;
+    zip.write_all(serde_json::to_string_pretty(&index)?.as_bytes())?;
+
+    zip.start_file("index.csv", options)?;
+    zip.write_all(&index_csv(index.iter())?)?;
+
+    zip.start_file("missing.csv", options)?;
+    zip.write_all(&index_csv(
+        index
+            .iter()
+            .filter(|e| matches!(e.status, "missing" | "file missing")),
+    )?)?;
+
+    zip.finish()?;
+
+    Ok(skipped)
+}
+
+fn index_csv<'a>(entries: impl Iterator<Item = &'a IndexEntry>) -> Result<Vec<u8>> {
+    let mut writer = csv::Writer::from_writer(Vec::new());
+    writer.write_record([
+        "row",
+        "date",
+        "amount",
+        "currency",
+        "description",
+        "category",
+        "status",
+        "files",
+        "missing files",
+    ])?;
+    for e in entries {
+        writer.write_record([
+            e.row.to_string().as_str(),
+            &e.date,
+            &e.amount,
+            &e.currency,
+            &e.description,
+            &e.category,
+            e.status,
+            &e.files.join("; "),
+            &e.missing_files.join("; "),
+        ])?;
+    }
+    Ok(writer.into_inner()?)
+}
+
+static CONVERTED: AtomicUsize = AtomicUsize::new(0);
+
+/// images named `.pdf` are converted like they would be when renaming
+fn receipt_content(source: &Path, name: &str) -> Result<Vec<u8>> {
+    let is_conversion = ReceiptKind::from_path(source) == Some(ReceiptKind::Image)
+        && ReceiptKind::from_path(Path::new(name)) == Some(ReceiptKind::Pdf);
+
+    if !is_conversion {
+        return std::fs::read(source).with_context(|| format!("reading {}", source.display()));
+    }
+
+    // numbered so concurrent exports do not share a file
+    let converted = std::env::temp_dir().join(format!(
+        "ccaccounting-export-{}-{}-{}",
+        std::process::id(),
+        CONVERTED.fetch_add(1, Ordering::Relaxed),
+        name
+    ));
+    pdf::image_to_pdf(source, &converted)
+        .with_context(|| format!("converting {}", source.display()))?;
+    let content = std::fs::read(&converted);
+    if let Err(e) = std::fs::remove_file(&converted) {
+        tracing::warn!("removing converted receipt failed: {}", e);
+    }
+
+    Ok(content?)
+}
+
+/// receipts of different rows may end up with the same target name
+fn unique_name(name: &str, names: &HashSet<String>) -> String {
+    if !names.contains(name) {
+        return name.to_string();
+    }
+
+    let path = Path::new(name);
+    let stem = path
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_default();
+    let ext = path
+        .extension()
+        .map(|e| format!(".{}", e.to_string_lossy()))
+        .unwrap_or_default();
+
+    (2..)
+        .map(|n| format!("{stem}-{n}{ext}"))
+        .find(|candidate| !names.contains(candidate))
+        .unwrap_or_default()
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ExportFormat {
+    /// zip of the renamed receipts with an index
+    Package,
+    /// single pdf with a cover sheet and all receipts
+    Bundle,
+}
+
+impl ExportFormat {
+    pub fn extension(self) -> &'static str {
+        match self {
+            ExportFormat::Package => "zip",
+            ExportFormat::Bundle => "pdf",
+        }
+    }
+
+    /// returns the receipts left out
+    fn write(self, target: &Path, title: &str, rows: &[PackageRow]) -> Result<Vec<PathBuf>> {
+        match self {
+            ExportFormat::Package => write_package(target, rows),
+            ExportFormat::Bundle => bundle::write_bundle(target, title, rows),
+        }
+    }
+}
+
+/// picks the target file and writes the export in the background
+#[derive(Debug, Default)]
+pub struct PackageExport {
+    rows: Vec<PackageRow>,
+    format: Option<ExportFormat>,
+    title: String,
+    wait_for_target: Option<Receiver<Option<PathBuf>>>,
+    pending: Option<Receiver<Result<Exported>>>,
+}
+
+impl PackageExport {
+    /// `title` names the statement on the cover of a bundle
+    pub fn start(
+        &mut self,
+        rows: Vec<PackageRow>,
+        format: ExportFormat,
+        title: String,
+        file_name: String,
+    ) {
+        let main = dispatch::Queue::main();
+
+        let (tx, rx) = channel();
+
+        let extension = format.extension();
+        main.exec_async(move || {
+            let path = FileDialog::new()
+                .add_filter(extension, &[extension])
+                .set_file_name(&file_name)
+                .save_file();
+
+            tx.send(path).unwrap_or_default();
+        });
+
+        self.rows = rows;
+        self.format = Some(format);
+        self.title = title;
+        self.wait_for_target = Some(rx);
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.wait_for_target.is_some() || self.pending.is_some()
+    }
+
+    /// returns the written export once done
+    pub fn poll(&mut self) -> Option<Result<Exported>> {
+        if let Some(receiver) = self.wait_for_target.as_ref() {
+            match receiver.try_recv() {
+                Ok(Some(target)) => {
+                    self.wait_for_target = None;
+
+                    let rows = std::mem::take(&mut self.rows);
+                    let title = std::mem::take(&mut self.title);
+                    let format = self.format.take().unwrap_or(ExportFormat::Package);
+                    let (tx, rx) = channel();
+                    std::thread::spawn(move || {
+                        let res = format
+                            .write(&target, &title, &rows)
+                            .map(|skipped| Exported {
+                                path: target,
+                                skipped,
+                            });
+                        tx.send(res).ok();
+                    });
+                    self.pending = Some(rx);
+                }
+                Ok(None) | Err(TryRecvError::Disconnected) => {
+                    self.wait_for_target = None;
+                    self.rows.clear();
+                    self.format = None;
+                }
+                Err(TryRecvError::Empty) => {}
+            }
+        }
+
+        let res = self.pending.as_ref()?.try_recv();
+        match res {
+            Ok(res) => {
+                self.pending = None;
+                Some(res)
+            }
+            Err(TryRecvError::Empty) => None,
+            Err(TryRecvError::Disconnected) => {
+                self.pending = None;
+                Some(Err(anyhow::anyhow!("export stopped unexpectedly")))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdir::TestDir;
+    use std::io::Read;
+
+    #[test]
+    fn package_skips_missing_receipts() {
+        let dir = TestDir::new("export");
+        let receipt = dir.join("scan.pdf");
+        std::fs::write(&receipt, "pdf").unwrap();
+        let missing = dir.join("gone.pdf");
+
+        let row = |row, receipts: Vec<(PathBuf, String)>| PackageRow {
+            row,
+            transaction: None,
+            hidden: false,
+            category: None,
+            no_receipt: None,
+            receipts,
+        };
+        let rows = vec![
+            row(0, vec![(receipt.clone(), "000-hotel.pdf".to_string())]),
+            row(1, vec![(missing.clone(), "001-taxi.pdf".to_string())]),
+            // a shared receipt is added once
+            row(2, vec![(receipt.clone(), "002-hotel.pdf".to_string())]),
+        ];
+
+        let target = dir.join("package.zip");
+        let skipped = write_package(&target, &rows).unwrap();
+        assert_eq!(skipped, vec![missing]);
+        assert!(!dir.join("package.tmp").exists());
+
+        let mut zip = zip::ZipArchive::new(File::open(&target).unwrap()).unwrap();
+        let mut names: Vec<&str> = zip.file_names().collect();
+        names.sort();
+        assert_eq!(
+            names,
+            vec![
+                "index.csv",
+                "index.json",
+                "missing.csv",
+                "receipts/000-hotel.pdf"
+            ]
+        );
+
+        let mut missing_csv = String::new();
+        zip.by_name("missing.csv")
+            .unwrap()
+            .read_to_string(&mut missing_csv)
+            .unwrap();
+        let lines: Vec<&str> = missing_csv.lines().collect();
+        assert_eq!(lines.len(), 2);
+        assert!(lines[1].starts_with("1,") && lines[1].contains("file missing"));
+    }
+}
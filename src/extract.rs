@@ -0,0 +1,80 @@
+use anyhow::{anyhow, Result};
+use chrono::NaiveDate;
+use regex::Regex;
+use std::{path::Path, sync::OnceLock};
+
+use crate::{
+    format::{read_date, DATE_FORMATS},
+    receipttype::ReceiptKind,
+    transaction::Amount,
+};
+
+/// facts found in the text of a receipt used for matching
+#[derive(Debug, Clone, Default)]
+pub struct ReceiptFacts {
+    pub amounts: Vec<Amount>,
+    pub dates: Vec<NaiveDate>,
+    /// lowercase text including the file name
+    pub text: String,
+}
+
+/// text of a receipt, receipts without a text layer go through OCR if enabled
+#[cfg_attr(not(feature = "ocr"), allow(unused_variables))]
+pub fn receipt_text(path: &Path, ocr_cache: &Path) -> Result<String> {
+    let kind = ReceiptKind::from_path(path)
+        .ok_or_else(|| anyhow!("unsupported receipt type: {:?}", path))?;
+    let text = kind.extract_text(path);
+
+    #[cfg(feature = "ocr")]
+    if kind.needs_ocr() && text.as_ref().map(|t| t.trim().is_empty()).unwrap_or(true) {
+        return crate::ocr::cached_text(path, ocr_cache);
+    }
+
+    text
+}
+
+pub fn pdf_text(path: &Path) -> Result<String> {
+    let path = path.to_path_buf();
+    // pdf-extract panics on some malformed files
+    std::panic::catch_unwind(move || pdf_extract::extract_text(&path))
+        .map_err(|_| anyhow!("text extraction panicked"))?
+        .map_err(|e| anyhow!("text extraction failed: {}", e))
+}
+
+pub fn facts(path: &Path, text: &str) -> ReceiptFacts {
+    static AMOUNT: OnceLock<Regex> = OnceLock::new();
+    static DATE: OnceLock<Regex> = OnceLock::new();
+
+    let amount = AMOUNT
+        .get_or_init(|| Regex::new(r"-?\d{1,3}(?:[.,' ]?\d{3})*[.,]\d{2}\b").expect("valid regex"));
+    let date = DATE.get_or_init(|| {
+        Regex::new(r"\b(\d{1,2}\.\d{1,2}\.\d{2,4}|\d{4}-\d{2}-\d{2}|\d{1,2}/\d{1,2}/\d{4})\b")
+            .expect("valid regex")
+    });
+
+    let mut amounts: Vec<Amount> = amount
+        .find_iter(text)
+        .filter_map(|m| Amount::parse(m.as_str()).ok())
+        .map(Amount::abs)
+        .collect();
+    amounts.sort();
+    amounts.dedup();
+
+    let mut dates: Vec<NaiveDate> = date
+        .find_iter(text)
+        .filter_map(|m| DATE_FORMATS.iter().find_map(|f| read_date(m.as_str(), f)))
+        .collect();
+    dates.sort();
+    dates.dedup();
+
+    let file_name = path
+        .file_name()
+        .map(|f| f.to_string_lossy().to_lowercase())
+        .unwrap_or_default();
+
+    ReceiptFacts {
+        amounts,
+        dates,
+        text: format!("{} {}", file_name, text.to_lowercase()),
+    }
+}
@@ -0,0 +1,354 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use std::cmp::Ordering;
+
+use crate::{
+    format::{read_date, DATE_FORMATS},
+    transaction::{Amount, Transaction},
+};
+
+const DATE_FORMAT: &str = "%Y-%m-%d";
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum StatusFilter {
+    #[default]
+    All,
+    Unassigned,
+    Assigned,
+    WronglyNamed,
+    Hidden,
+}
+
+impl StatusFilter {
+    pub const ALL: [StatusFilter; 5] = [
+        StatusFilter::All,
+        StatusFilter::Unassigned,
+        StatusFilter::Assigned,
+        StatusFilter::WronglyNamed,
+        StatusFilter::Hidden,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            StatusFilter::All => "all",
+            StatusFilter::Unassigned => "unassigned",
+            StatusFilter::Assigned => "assigned",
+            StatusFilter::WronglyNamed => "wrongly named",
+            StatusFilter::Hidden => "hidden",
+        }
+    }
+}
+
+/// what a row offers to be filtered on
+#[derive(Debug)]
+pub struct RowStatus {
+    pub hidden: bool,
+    pub assigned: bool,
+    pub wrongly_named: bool,
+}
+
+/// inputs of the filter bar, empty fields do not filter
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TableFilter {
+    pub text: String,
+    pub min_amount: String,
+    pub max_amount: String,
+    /// `YYYY-MM-DD`
+    pub from: String,
+    pub to: String,
+    pub status: StatusFilter,
+}
+
+impl TableFilter {
+    pub fn is_active(&self) -> bool {
+        *self != Self::default()
+    }
+
+    /// amounts are compared without their sign
+    pub fn min_amount(&self) -> Result<Option<Amount>> {
+        parse_optional(&self.min_amount, |s| Ok(Amount::parse(s)?.abs()))
+    }
+
+    pub fn max_amount(&self) -> Result<Option<Amount>> {
+        parse_optional(&self.max_amount, |s| Ok(Amount::parse(s)?.abs()))
+    }
+
+    pub fn from(&self) -> Result<Option<NaiveDate>> {
+        parse_optional(&self.from, |s| {
+            read_date(s, DATE_FORMAT).ok_or_else(|| anyhow::anyhow!("invalid date '{s}'"))
+        })
+    }
+
+    pub fn to(&self) -> Result<Option<NaiveDate>> {
+        parse_optional(&self.to, |s| {
+            read_date(s, DATE_FORMAT).ok_or_else(|| anyhow::anyhow!("invalid date '{s}'"))
+        })
+    }
+
+    /// parses the inputs once for checking all rows, invalid bounds are shown in the
+    /// filter bar and ignored here
+    pub fn active(&self) -> ActiveFilter {
+        ActiveFilter {
+            text: self.text.trim().to_lowercase(),
+            min_amount: self.min_amount().ok().flatten(),
+            max_amount: self.max_amount().ok().flatten(),
+            from: self.from().ok().flatten(),
+            to: self.to().ok().flatten(),
+            status: self.status,
+        }
+    }
+}
+
+/// the filter with its inputs parsed
+#[derive(Debug, Clone, Default)]
+pub struct ActiveFilter {
+    text: String,
+    min_amount: Option<Amount>,
+    max_amount: Option<Amount>,
+    from: Option<NaiveDate>,
+    to: Option<NaiveDate>,
+    status: StatusFilter,
+}
+
+impl ActiveFilter {
+    /// `show_hidden` only matters unless hidden rows are asked for explicitly
+    pub fn matches(
+        &self,
+        cells: &[String],
+        transaction: Option<&Transaction>,
+        status: &RowStatus,
+        show_hidden: bool,
+    ) -> bool {
+        let status_matches = match self.status {
+            StatusFilter::All => show_hidden || !status.hidden,
+            StatusFilter::Hidden => status.hidden,
+            StatusFilter::Unassigned => (show_hidden || !status.hidden) && !status.assigned,
+            StatusFilter::Assigned => (show_hidden || !status.hidden) && status.assigned,
+            StatusFilter::WronglyNamed => (show_hidden || !status.hidden) && status.wrongly_named,
+        };
+        if !status_matches {
+            return false;
+        }
+
+        if !self.text.is_empty() && !cells.iter().any(|c| c.to_lowercase().contains(&self.text)) {
+            return false;
+        }
+
+        let amount = transaction.map(|t| t.amount.abs());
+        if let Some(min) = self.min_amount {
+            if amount.map_or(true, |a| a < min) {
+                return false;
+            }
+        }
+        if let Some(max) = self.max_amount {
+            if amount.map_or(true, |a| a > max) {
+                return false;
+            }
+        }
+
+        let date = transaction.map(|t| t.date);
+        if let Some(from) = self.from {
+            if date.map_or(true, |d| d < from) {
+                return false;
+            }
+        }
+        if let Some(to) = self.to {
+            if date.map_or(true, |d| d > to) {
+                return false;
+            }
+        }
+
+        true
+    }
+}
+
+fn parse_optional<T>(input: &str, parse: impl FnOnce(&str) -> Result<T>) -> Result<Option<T>> {
+    let input = input.trim();
+    if input.is_empty() {
+        return Ok(None);
+    }
+    parse(input).map(Some)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SortColumn {
+    /// position in the csv
+    Index,
+    Cell(usize),
+    Receipt,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Sort {
+    pub column: SortColumn,
+    pub ascending: bool,
+}
+
+impl Sort {
+    /// clicking the sorted column again flips the direction
+    pub fn toggle(current: Option<Sort>, column: SortColumn) -> Option<Sort> {
+        match current {
+            Some(sort) if sort.column == column && sort.ascending => Some(Sort {
+                column,
+                ascending: false,
+            }),
+            Some(sort) if sort.column == column => None,
+            _ => Some(Sort {
+                column,
+                ascending: true,
+            }),
+        }
+    }
+
+    pub fn arrow(current: Option<Sort>, column: SortColumn) -> &'static str {
+        match current {
+            Some(sort) if sort.column == column && sort.ascending => " ⏶",
+            Some(sort) if sort.column == column => " ⏷",
+            _ => "",
+        }
+    }
+}
+
+/// how the cells of a column are compared, picked once for the whole column
+/// so the order stays consistent
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CellOrder {
+    Date(&'static str),
+    Amount,
+    Text,
+}
+
+impl CellOrder {
+    /// dates if every cell is a date of the same format, else amounts if every cell is one,
+    /// else text, empty cells are left out
+    pub fn for_column<'a>(cells: impl Iterator<Item = &'a str>) -> Self {
+        let cells: Vec<&str> = cells.map(str::trim).filter(|c| !c.is_empty()).collect();
+        if cells.is_empty() {
+            return CellOrder::Text;
+        }
+
+        // dates first, `01.02.2023` would parse as an amount too
+        let date_format = DATE_FORMATS
+            .iter()
+            .find(|format| cells.iter().all(|c| read_date(c, format).is_some()));
+        if let Some(format) = date_format {
+            return CellOrder::Date(format);
+        }
+        if cells.iter().all(|c| Amount::parse(c).is_ok()) {
+            return CellOrder::Amount;
+        }
+        CellOrder::Text
+    }
+
+    /// empty cells come first
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        let (a, b) = (a.trim(), b.trim());
+        match self {
+            CellOrder::Date(format) => {
+                let date = |s: &str| read_date(s, format);
+                date(a).cmp(&date(b))
+            }
+            CellOrder::Amount => Amount::parse(a).ok().cmp(&Amount::parse(b).ok()),
+            CellOrder::Text => a.to_lowercase().cmp(&b.to_lowercase()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(cells: &[&str]) -> Vec<String> {
+        let order = CellOrder::for_column(cells.iter().copied());
+        let mut cells: Vec<String> = cells.iter().map(|c| c.to_string()).collect();
+        cells.sort_by(|a, b| order.compare(a, b));
+        cells
+    }
+
+    #[test]
+    fn column_order() {
+        assert_eq!(
+            CellOrder::for_column(["01.02.2023", "", "31.12.2022"].into_iter()),
+            CellOrder::Date("%d.%m.%Y")
+        );
+        assert_eq!(
+            CellOrder::for_column(["12,34", "-1.000,00", "5"].into_iter()),
+            CellOrder::Amount
+        );
+        assert_eq!(
+            CellOrder::for_column(["10", "1x", "AMAZON 12", "shop"].into_iter()),
+            CellOrder::Text
+        );
+        assert_eq!(
+            CellOrder::for_column(["", " "].into_iter()),
+            CellOrder::Text
+        );
+    }
+
+    #[test]
+    fn sort_cells() {
+        assert_eq!(
+            sorted(&["02.01.2023", "", "31.12.2022", "01.02.2023"]),
+            vec!["", "31.12.2022", "02.01.2023", "01.02.2023"]
+        );
+        assert_eq!(sorted(&["10", "9", "-1,50"]), vec!["-1,50", "9", "10"]);
+        // mixed cells are compared as text only, which is transitive
+        assert_eq!(
+            sorted(&["10", "1x", "9", "B", "a"]),
+            vec!["10", "1x", "9", "a", "B"]
+        );
+    }
+
+    #[test]
+    fn filter_rows() {
+        let transaction = Transaction {
+            date: NaiveDate::from_ymd_opt(2023, 1, 15).unwrap(),
+            amount: Amount::parse("-25,00").unwrap(),
+            currency: "EUR".to_string(),
+            description: "Hotel".to_string(),
+        };
+        let cells = vec!["15.01.2023".to_string(), "Hotel Berlin".to_string()];
+        let status = RowStatus {
+            hidden: false,
+            assigned: false,
+            wrongly_named: false,
+        };
+        let matches = |filter: TableFilter| {
+            filter
+                .active()
+                .matches(&cells, Some(&transaction), &status, false)
+        };
+
+        assert!(matches(TableFilter::default()));
+        assert!(matches(TableFilter {
+            text: " berlin".to_string(),
+            min_amount: "20".to_string(),
+            max_amount: "25,00".to_string(),
+            from: "2023-01-15".to_string(),
+            to: "2023-01-31".to_string(),
+            ..Default::default()
+        }));
+        assert!(!matches(TableFilter {
+            text: "paris".to_string(),
+            ..Default::default()
+        }));
+        assert!(!matches(TableFilter {
+            min_amount: "25,01".to_string(),
+            ..Default::default()
+        }));
+        assert!(!matches(TableFilter {
+            from: "2023-01-16".to_string(),
+            ..Default::default()
+        }));
+        assert!(!matches(TableFilter {
+            status: StatusFilter::Assigned,
+            ..Default::default()
+        }));
+        // invalid bounds do not filter
+        assert!(matches(TableFilter {
+            max_amount: "abc".to_string(),
+            to: "yesterday".to_string(),
+            ..Default::default()
+        }));
+    }
+}
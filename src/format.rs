@@ -0,0 +1,416 @@
+use anyhow::Result;
+use chrono::{Datelike, NaiveDate};
+
+use crate::{project::CsvRow, transaction::Amount};
+
+/// roles a column of a statement can have
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct ColumnMapping {
+    pub booking_date: Option<usize>,
+    pub value_date: Option<usize>,
+    pub description: Option<usize>,
+    pub amount: Option<usize>,
+    pub currency: Option<usize>,
+    pub foreign_amount: Option<usize>,
+    /// charges are listed as positive amounts and refunds as negative ones
+    pub charges_positive: bool,
+}
+
+impl ColumnMapping {
+    pub fn roles(&self) -> [(&'static str, Option<usize>); 6] {
+        [
+            ("Booking Date", self.booking_date),
+            ("Value Date", self.value_date),
+            ("Description", self.description),
+            ("Amount", self.amount),
+            ("Currency", self.currency),
+            ("Foreign Amount", self.foreign_amount),
+        ]
+    }
+
+    pub fn roles_mut(&mut self) -> [(&'static str, &mut Option<usize>); 6] {
+        [
+            ("Booking Date", &mut self.booking_date),
+            ("Value Date", &mut self.value_date),
+            ("Description", &mut self.description),
+            ("Amount", &mut self.amount),
+            ("Currency", &mut self.currency),
+            ("Foreign Amount", &mut self.foreign_amount),
+        ]
+    }
+
+    pub fn role_of(&self, column: usize) -> Option<&'static str> {
+        self.roles()
+            .into_iter()
+            .find(|(_, c)| *c == Some(column))
+            .map(|(name, _)| name)
+    }
+
+    /// date, description and amount are needed for renaming
+    pub fn is_complete(&self) -> bool {
+        self.booking_date.is_some() && self.description.is_some() && self.amount.is_some()
+    }
+}
+
+/// date formats the statements and receipts use
+pub(crate) const DATE_FORMATS: &[&str] =
+    &["%d.%m.%Y", "%d.%m.%y", "%Y-%m-%d", "%d/%m/%Y", "%m/%d/%Y"];
+
+/// parses a date with one format, `%Y` also takes two digits in chrono so short years are
+/// refused and left to `%y`, `03.01.23` would be in the year 23 otherwise
+pub(crate) fn read_date(input: &str, format: &str) -> Option<NaiveDate> {
+    let date = NaiveDate::parse_from_str(input, format).ok()?;
+    if format.contains("%Y") && !(1000..=9999).contains(&date.year()) {
+        return None;
+    }
+    Some(date)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, serde::Deserialize, serde::Serialize)]
+pub enum FormatKind {
+    MilesAndMore,
+    Amex,
+    Barclaycard,
+    Dkb,
+    N26,
+    Generic { delimiter: u8 },
+}
+
+impl FormatKind {
+    const KNOWN: [FormatKind; 5] = [
+        FormatKind::MilesAndMore,
+        FormatKind::Amex,
+        FormatKind::Barclaycard,
+        FormatKind::Dkb,
+        FormatKind::N26,
+    ];
+
+    pub fn format(self) -> Box<dyn StatementFormat> {
+        match self {
+            FormatKind::MilesAndMore => Box::new(MilesAndMore),
+            FormatKind::Amex => Box::new(Amex),
+            FormatKind::Barclaycard => Box::new(Barclaycard),
+            FormatKind::Dkb => Box::new(Dkb),
+            FormatKind::N26 => Box::new(N26),
+            FormatKind::Generic { delimiter } => Box::new(Generic { delimiter }),
+        }
+    }
+}
+
+pub trait StatementFormat: std::fmt::Debug {
+    fn name(&self) -> &'static str;
+    fn delimiter(&self) -> u8;
+
+    /// number of records preceding the first transaction (including the header)
+    fn header_rows(&self) -> usize {
+        1
+    }
+
+    /// leading cells of the header row used to recognize the format
+    fn header(&self) -> &'static [&'static str];
+
+    fn columns(&self) -> ColumnMapping;
+
+    /// date formats tried in order when parsing transactions
+    fn date_formats(&self) -> &'static [&'static str] {
+        DATE_FORMATS
+    }
+
+    fn currency(&self) -> &'static str {
+        "EUR"
+    }
+
+    fn detect(&self, records: &[CsvRow]) -> bool {
+        let header = self.header();
+        if header.is_empty() {
+            return false;
+        }
+
+        self.header_rows()
+            .checked_sub(1)
+            .and_then(|idx| records.get(idx))
+            .map(|row| {
+                row.cells.len() >= header.len()
+                    && header
+                        .iter()
+                        .zip(row.cells.iter())
+                        .all(|(expected, cell)| cell.trim().eq_ignore_ascii_case(expected))
+            })
+            .unwrap_or_default()
+    }
+}
+
+#[derive(Debug)]
+pub struct MilesAndMore;
+
+impl StatementFormat for MilesAndMore {
+    fn name(&self) -> &'static str {
+        "Miles & More"
+    }
+
+    fn delimiter(&self) -> u8 {
+        b';'
+    }
+
+    fn header(&self) -> &'static [&'static str] {
+        &["Belegdatum", "Buchungsdatum", "Beschreibung", "Betrag"]
+    }
+
+    fn columns(&self) -> ColumnMapping {
+        ColumnMapping {
+            booking_date: Some(0),
+            value_date: Some(1),
+            description: Some(2),
+            amount: Some(3),
+            currency: Some(4),
+            foreign_amount: None,
+            charges_positive: false,
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Amex;
+
+impl StatementFormat for Amex {
+    fn name(&self) -> &'static str {
+        "American Express"
+    }
+
+    fn delimiter(&self) -> u8 {
+        b','
+    }
+
+    fn header(&self) -> &'static [&'static str] {
+        &["Datum", "Beschreibung", "Betrag"]
+    }
+
+    fn date_formats(&self) -> &'static [&'static str] {
+        &["%d/%m/%Y", "%d.%m.%Y"]
+    }
+
+    fn columns(&self) -> ColumnMapping {
+        ColumnMapping {
+            booking_date: Some(0),
+            description: Some(1),
+            amount: Some(2),
+            charges_positive: true,
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Barclaycard;
+
+impl StatementFormat for Barclaycard {
+    fn name(&self) -> &'static str {
+        "Barclaycard"
+    }
+
+    fn delimiter(&self) -> u8 {
+        b';'
+    }
+
+    fn header(&self) -> &'static [&'static str] {
+        &[
+            "Referenznummer",
+            "Buchungsdatum",
+            "Transaktionsdatum",
+            "Betrag",
+            "Beschreibung",
+        ]
+    }
+
+    fn columns(&self) -> ColumnMapping {
+        ColumnMapping {
+            booking_date: Some(1),
+            value_date: Some(2),
+            amount: Some(3),
+            description: Some(4),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct Dkb;
+
+impl StatementFormat for Dkb {
+    fn name(&self) -> &'static str {
+        "DKB"
+    }
+
+    fn delimiter(&self) -> u8 {
+        b';'
+    }
+
+    fn header_rows(&self) -> usize {
+        // card number, period from/to, balance and date precede the header
+        6
+    }
+
+    fn header(&self) -> &'static [&'static str] {
+        &[
+            "Umsatz abgerechnet und nicht im Saldo enthalten",
+            "Wertstellung",
+            "Belegdatum",
+            "Beschreibung",
+            "Betrag (EUR)",
+        ]
+    }
+
+    fn columns(&self) -> ColumnMapping {
+        ColumnMapping {
+            value_date: Some(1),
+            booking_date: Some(2),
+            description: Some(3),
+            amount: Some(4),
+            foreign_amount: Some(5),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug)]
+pub struct N26;
+
+impl StatementFormat for N26 {
+    fn name(&self) -> &'static str {
+        "N26"
+    }
+
+    fn delimiter(&self) -> u8 {
+        b','
+    }
+
+    fn header(&self) -> &'static [&'static str] {
+        &[
+            "Date",
+            "Payee",
+            "Account number",
+            "Transaction type",
+            "Payment reference",
+            "Amount (EUR)",
+        ]
+    }
+
+    fn date_formats(&self) -> &'static [&'static str] {
+        &["%Y-%m-%d"]
+    }
+
+    fn columns(&self) -> ColumnMapping {
+        ColumnMapping {
+            booking_date: Some(0),
+            description: Some(1),
+            amount: Some(5),
+            foreign_amount: Some(6),
+            ..Default::default()
+        }
+    }
+}
+
+/// fallback for unknown exports: `date, description, amount`
+#[derive(Debug)]
+pub struct Generic {
+    delimiter: u8,
+}
+
+impl StatementFormat for Generic {
+    fn name(&self) -> &'static str {
+        "Generic"
+    }
+
+    fn delimiter(&self) -> u8 {
+        self.delimiter
+    }
+
+    fn header(&self) -> &'static [&'static str] {
+        &[]
+    }
+
+    fn columns(&self) -> ColumnMapping {
+        ColumnMapping {
+            booking_date: Some(0),
+            description: Some(1),
+            amount: Some(2),
+            ..Default::default()
+        }
+    }
+}
+
+const DETECT_SAMPLE_ROWS: usize = 20;
+
+pub fn detect(content: &[u8]) -> FormatKind {
+    for kind in FormatKind::KNOWN {
+        let format = kind.format();
+        let sample =
+            read_records(content, format.delimiter(), Some(DETECT_SAMPLE_ROWS)).unwrap_or_default();
+        if format.detect(&sample) {
+            return kind;
+        }
+    }
+
+    FormatKind::Generic {
+        delimiter: sniff_delimiter(content),
+    }
+}
+
+fn sniff_delimiter(content: &[u8]) -> u8 {
+    let first_line = content.split(|c| *c == b'\n').next().unwrap_or_default();
+
+    [b';', b',', b'\t']
+        .into_iter()
+        .max_by_key(|d| first_line.iter().filter(|c| *c == d).count())
+        .unwrap_or(b';')
+}
+
+/// separates the header row from the transactions, generic exports may come without one
+pub fn split_header(
+    format: &dyn StatementFormat,
+    mut records: Vec<CsvRow>,
+) -> (Option<CsvRow>, Vec<CsvRow>) {
+    let has_header = !format.header().is_empty() || records.first().is_some_and(is_header);
+    let skip = if has_header { format.header_rows() } else { 0 };
+
+    let rows = records.split_off(skip.min(records.len()));
+    let header = skip
+        .checked_sub(1)
+        .and_then(|idx| records.get(idx))
+        .cloned();
+
+    (header, rows)
+}
+
+/// a header row has titles but no dates or amounts
+fn is_header(row: &CsvRow) -> bool {
+    let cells = || row.cells.iter().map(|c| c.trim()).filter(|c| !c.is_empty());
+
+    cells().next().is_some()
+        && cells().all(|cell| {
+            Amount::parse(cell).is_err()
+                && !DATE_FORMATS.iter().any(|f| read_date(cell, f).is_some())
+        })
+}
+
+pub fn read_records(content: &[u8], delimiter: u8, limit: Option<usize>) -> Result<Vec<CsvRow>> {
+    let mut rdr = csv::ReaderBuilder::new()
+        .flexible(true)
+        .has_headers(false)
+        .delimiter(delimiter)
+        .from_reader(content);
+
+    let mut rows = Vec::new();
+    for result in rdr.byte_records().take(limit.unwrap_or(usize::MAX)) {
+        let result = result?;
+        let mut row = Vec::new();
+        for result in result.iter() {
+            row.push(String::from_utf8_lossy(result).to_string());
+        }
+
+        rows.push(CsvRow { cells: row });
+    }
+
+    Ok(rows)
+}
@@ -0,0 +1,327 @@
+use anyhow::{bail, Context, Result};
+use std::{collections::BTreeMap, path::Path};
+
+use crate::{pdf, receipttype::ReceiptKind, rowmetadata::RowMetaData};
+
+const MAX_HISTORY: usize = 100;
+
+#[derive(Debug, Clone)]
+pub enum Command {
+    /// meta data of a row changed (assignment, clear, hide)
+    Edit {
+        row: String,
+        before: RowMetaData,
+        after: RowMetaData,
+    },
+    /// receipt of a row was renamed on disk
+    Rename {
+        row: String,
+        from: String,
+        to: String,
+    },
+    /// image receipt was converted to a pdf, the image is kept in `stash` for undo
+    Convert {
+        row: String,
+        from: String,
+        to: String,
+        stash: String,
+    },
+    Batch(Vec<Command>),
+}
+
+impl Command {
+    pub fn edit(row: &str, before: &RowMetaData, edit: impl FnOnce(&mut RowMetaData)) -> Self {
+        let mut after = before.clone();
+        edit(&mut after);
+        Self::Edit {
+            row: row.to_string(),
+            before: before.clone(),
+            after,
+        }
+    }
+
+    /// renames a receipt, images get converted if the target is a pdf
+    pub fn rename(row: &str, from: String, to: String, stash_dir: &Path) -> Self {
+        let is_conversion = ReceiptKind::from_path(Path::new(&from)) == Some(ReceiptKind::Image)
+            && ReceiptKind::from_path(Path::new(&to)) == Some(ReceiptKind::Pdf);
+
+        if !is_conversion {
+            return Self::Rename {
+                row: row.to_string(),
+                from,
+                to,
+            };
+        }
+
+        let file_name = Path::new(&from)
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        let stash = stash_dir.join(format!(
+            "{}-{}",
+            chrono::Local::now().timestamp_millis(),
+            file_name
+        ));
+
+        Self::Convert {
+            row: row.to_string(),
+            from,
+            to,
+            stash: stash.to_string_lossy().to_string(),
+        }
+    }
+
+    fn apply(&self, rows: &mut BTreeMap<String, RowMetaData>, forward: bool) -> Result<()> {
+        match self {
+            Command::Edit { row, before, after } => {
+                let meta = if forward { after } else { before };
+                rows.insert(row.clone(), meta.clone());
+            }
+            Command::Rename { row, from, to } => {
+                let (from, to) = if forward { (from, to) } else { (to, from) };
+
+                tracing::debug!("rename: '{}' -> '{}'", from, to);
+
+                if from.to_lowercase() != to.to_lowercase() && Path::new(to).exists() {
+                    bail!("'{}' already exists", to);
+                }
+
+                std::fs::rename(from, to).with_context(|| format!("rename '{from}' -> '{to}'"))?;
+                replace_receipt(rows, row, from, to);
+            }
+            Command::Convert {
+                row,
+                from,
+                to,
+                stash,
+            } => {
+                if forward {
+                    tracing::debug!("convert: '{}' -> '{}'", from, to);
+
+                    pdf::image_to_pdf(Path::new(from), Path::new(to))
+                        .with_context(|| format!("convert '{from}' -> '{to}'"))?;
+                    if let Some(dir) = Path::new(stash).parent() {
+                        std::fs::create_dir_all(dir)?;
+                    }
+                    if let Err(e) = std::fs::rename(from, stash) {
+                        std::fs::remove_file(to)?;
+                        return Err(e).with_context(|| format!("move '{from}' -> '{stash}'"));
+                    }
+                    replace_receipt(rows, row, from, to);
+                } else {
+                    if Path::new(from).exists() {
+                        bail!("'{}' already exists", from);
+                    }
+
+                    std::fs::rename(stash, from)
+                        .with_context(|| format!("restore '{stash}' -> '{from}'"))?;
+                    std::fs::remove_file(to).with_context(|| format!("remove '{to}'"))?;
+                    replace_receipt(rows, row, to, from);
+                }
+            }
+            Command::Batch(commands) => {
+                let ordered: Vec<&Command> = if forward {
+                    commands.iter().collect()
+                } else {
+                    commands.iter().rev().collect()
+                };
+
+                for (idx, cmd) in ordered.iter().enumerate() {
+                    if let Err(e) = cmd.apply(rows, forward) {
+                        // roll back what was applied so far
+                        for cmd in ordered[..idx].iter().rev() {
+                            if let Err(e) = cmd.apply(rows, !forward) {
+                                tracing::error!("rollback error: {:#}", e);
+                            }
+                        }
+                        return Err(e);
+                    }
+                }
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// points every row using the receipt to its new location, shared receipts included
+fn replace_receipt(rows: &mut BTreeMap<String, RowMetaData>, row: &str, from: &str, to: &str) {
+    rows.entry(row.to_string())
+        .or_default()
+        .replace_receipt(from, to);
+    for meta in rows.values_mut() {
+        if meta.receipt_paths().any(|p| p == from) {
+            meta.replace_receipt(from, to);
+        }
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+}
+
+impl History {
+    pub fn execute(
+        &mut self,
+        cmd: Command,
+        rows: &mut BTreeMap<String, RowMetaData>,
+    ) -> Result<()> {
+        cmd.apply(rows, true)?;
+
+        self.undo.push(cmd);
+        if self.undo.len() > MAX_HISTORY {
+            self.undo.remove(0);
+        }
+        self.redo.clear();
+
+        Ok(())
+    }
+
+    pub fn undo(&mut self, rows: &mut BTreeMap<String, RowMetaData>) -> Result<()> {
+        if let Some(cmd) = self.undo.pop() {
+            if let Err(e) = cmd.apply(rows, false) {
+                self.undo.push(cmd);
+                return Err(e);
+            }
+            self.redo.push(cmd);
+        }
+        Ok(())
+    }
+
+    pub fn redo(&mut self, rows: &mut BTreeMap<String, RowMetaData>) -> Result<()> {
+        if let Some(cmd) = self.redo.pop() {
+            if let Err(e) = cmd.apply(rows, true) {
+                self.redo.push(cmd);
+                return Err(e);
+            }
+            self.undo.push(cmd);
+        }
+        Ok(())
+    }
+
+    pub fn can_undo(&self) -> bool {
+        !self.undo.is_empty()
+    }
+
+    pub fn can_redo(&self) -> bool {
+        !self.redo.is_empty()
+    }
+
+    /// forgets all commands, they do not apply to replaced meta data
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdir::TestDir;
+
+    fn rows() -> BTreeMap<String, RowMetaData> {
+        BTreeMap::from([("x".to_string(), RowMetaData::default())])
+    }
+
+    fn hide(rows: &BTreeMap<String, RowMetaData>, row: &str) -> Command {
+        Command::edit(row, &rows[row], |meta| meta.hidden = !meta.hidden)
+    }
+
+    fn rename(dir: &Path, row: &str, from: &str, to: &str) -> Command {
+        let path = |name| dir.join(name).to_string_lossy().to_string();
+        Command::rename(row, path(from), path(to), dir)
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut rows = rows();
+        let mut history = History::default();
+        assert!(!history.can_undo() && !history.can_redo());
+
+        history.execute(hide(&rows, "x"), &mut rows).unwrap();
+        assert!(rows["x"].hidden);
+        assert!(history.can_undo() && !history.can_redo());
+
+        history.undo(&mut rows).unwrap();
+        assert!(!rows["x"].hidden);
+        assert!(!history.can_undo() && history.can_redo());
+
+        history.redo(&mut rows).unwrap();
+        assert!(rows["x"].hidden);
+
+        // a new command drops what could be redone
+        history.undo(&mut rows).unwrap();
+        history.execute(hide(&rows, "x"), &mut rows).unwrap();
+        assert!(!history.can_redo());
+
+        history.clear();
+        assert!(!history.can_undo() && !history.can_redo());
+    }
+
+    #[test]
+    fn history_is_capped() {
+        let mut rows = rows();
+        let mut history = History::default();
+        for _ in 0..MAX_HISTORY + 5 {
+            history.execute(hide(&rows, "x"), &mut rows).unwrap();
+        }
+
+        let mut undone = 0;
+        while history.can_undo() {
+            history.undo(&mut rows).unwrap();
+            undone += 1;
+        }
+        assert_eq!(undone, MAX_HISTORY);
+        // the 5 oldest toggles stay applied
+        assert!(rows["x"].hidden);
+    }
+
+    #[test]
+    fn rename_undo_redo() {
+        let dir = TestDir::with_files("history-rename", &["a.pdf"]);
+        let mut rows = rows();
+        rows.get_mut("x")
+            .unwrap()
+            .assign(dir.join("a.pdf").to_string_lossy().to_string());
+        let mut history = History::default();
+
+        history
+            .execute(rename(&dir, "x", "a.pdf", "b.pdf"), &mut rows)
+            .unwrap();
+        assert!(dir.join("b.pdf").exists() && !dir.join("a.pdf").exists());
+        assert!(rows["x"].receipt_paths().all(|p| p.ends_with("b.pdf")));
+
+        history.undo(&mut rows).unwrap();
+        assert!(dir.join("a.pdf").exists() && !dir.join("b.pdf").exists());
+        assert!(rows["x"].receipt_paths().all(|p| p.ends_with("a.pdf")));
+
+        // an undo that would overwrite a file fails and can be retried
+        history.redo(&mut rows).unwrap();
+        std::fs::write(dir.join("a.pdf"), "other").unwrap();
+        assert!(history.undo(&mut rows).is_err());
+        assert!(history.can_undo());
+        assert!(dir.join("b.pdf").exists());
+    }
+
+    #[test]
+    fn batch_rolls_back() {
+        let dir = TestDir::with_files("history-batch", &["a.pdf", "c.pdf"]);
+        let mut rows = rows();
+        let mut history = History::default();
+
+        let batch = Command::Batch(vec![
+            hide(&rows, "x"),
+            rename(&dir, "x", "a.pdf", "b.pdf"),
+            // the target exists, so the whole batch is undone
+            rename(&dir, "x", "c.pdf", "b.pdf"),
+        ]);
+        assert!(history.execute(batch, &mut rows).is_err());
+
+        assert!(dir.join("a.pdf").exists() && dir.join("c.pdf").exists());
+        assert!(!dir.join("b.pdf").exists());
+        assert!(!rows["x"].hidden);
+        assert!(!history.can_undo());
+    }
+}
@@ -2,8 +2,36 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")] // hide console window on Windows in release
 
 mod app;
+mod backup;
+mod bundle;
+mod discovery;
+mod duplicates;
+mod export;
+mod extract;
+mod filter;
+mod format;
+mod history;
+mod mappingwizard;
+mod matcher;
+#[cfg(feature = "ocr")]
+mod ocr;
+mod pdf;
+mod preview;
 mod project;
+mod receipttype;
+mod reconcile;
+mod rename;
 mod rowmetadata;
+mod settings;
+mod state;
+mod summary;
+mod tablelayout;
+mod template;
+#[cfg(test)]
+mod testdir;
+mod thumbnail;
+mod transaction;
+mod watcher;
 
 // When compiling natively:
 #[cfg(not(target_arch = "wasm32"))]
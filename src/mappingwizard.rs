@@ -0,0 +1,95 @@
+use egui::{Button, ComboBox, Grid, ScrollArea};
+
+use crate::{format::ColumnMapping, project::CsvRow};
+
+const PREVIEW_ROWS: usize = 8;
+
+#[derive(Debug, Default)]
+pub struct MappingWizard {
+    open: bool,
+    mapping: ColumnMapping,
+}
+
+impl MappingWizard {
+    pub fn open(&mut self, mapping: ColumnMapping) {
+        self.mapping = mapping;
+        self.open = true;
+    }
+
+    /// returns the mapping once the user applied it
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        rows: &[CsvRow],
+        columns: usize,
+    ) -> Option<ColumnMapping> {
+        let mut result = None;
+        let mut open = self.open;
+
+        egui::Window::new("Column Mapping")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label("Mark which column of the statement holds what:");
+
+                Grid::new("mapping_roles").num_columns(2).show(ui, |ui| {
+                    for (name, role) in self.mapping.roles_mut() {
+                        ui.label(name);
+                        ComboBox::from_id_source(name)
+                            .selected_text(role.map_or("-".to_string(), |c| format!("#{c}")))
+                            .show_ui(ui, |ui| {
+                                ui.selectable_value(role, None, "-");
+                                for c in 0..columns {
+                                    ui.selectable_value(role, Some(c), format!("#{c}"));
+                                }
+                            });
+                        ui.end_row();
+                    }
+                });
+                ui.checkbox(
+                    &mut self.mapping.charges_positive,
+                    "Charges are positive amounts",
+                );
+
+                ui.separator();
+
+                ScrollArea::both().max_height(200.0).show(ui, |ui| {
+                    Grid::new("mapping_preview").striped(true).show(ui, |ui| {
+                        for c in 0..columns {
+                            match self.mapping.role_of(c) {
+                                Some(role) => ui.strong(format!("#{c} {role}")),
+                                None => ui.label(format!("#{c}")),
+                            };
+                        }
+                        ui.end_row();
+
+                        for row in rows.iter().take(PREVIEW_ROWS) {
+                            for c in 0..columns {
+                                ui.label(row.cell(Some(c)));
+                            }
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(self.mapping.is_complete(), Button::new("Apply"))
+                        .clicked()
+                    {
+                        result = Some(self.mapping);
+                        self.open = false;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.open = false;
+                    }
+                });
+            });
+
+        self.open &= open;
+
+        result
+    }
+}
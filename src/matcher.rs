@@ -0,0 +1,236 @@
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+    time::SystemTime,
+};
+
+use crate::{
+    duplicates::modified,
+    extract::{self, ReceiptFacts},
+    transaction::Transaction,
+};
+
+/// suggestions below this confidence are not shown
+const MIN_CONFIDENCE: f32 = 0.35;
+
+#[derive(Debug, Clone)]
+pub struct Suggestion {
+    pub receipt: PathBuf,
+    pub row: usize,
+    pub confidence: f32,
+}
+
+/// extracts receipt texts in the background and proposes transactions for receipts
+#[derive(Debug, Default)]
+pub struct Matcher {
+    ocr_cache: PathBuf,
+    /// facts with the modification time of the file they were read from
+    facts: HashMap<PathBuf, (Option<SystemTime>, ReceiptFacts)>,
+    pending: Option<Receiver<(PathBuf, Option<SystemTime>, ReceiptFacts)>>,
+    suggestions: Vec<Suggestion>,
+    rejected: HashSet<(PathBuf, usize)>,
+}
+
+impl Matcher {
+    pub fn new(ocr_cache: PathBuf) -> Self {
+        Self {
+            ocr_cache,
+            ..Default::default()
+        }
+    }
+
+    /// starts extracting the text of all receipts not seen before or changed since
+    pub fn scan(&mut self, receipts: &[PathBuf]) {
+        let todo: Vec<PathBuf> = receipts
+            .iter()
+            .filter(|p| {
+                self.facts
+                    .get(*p)
+                    .map(|(time, _)| *time != modified(p))
+                    .unwrap_or(true)
+            })
+            .cloned()
+            .collect();
+
+        if todo.is_empty() || self.pending.is_some() {
+            return;
+        }
+
+        let (tx, rx) = channel();
+        let ocr_cache = self.ocr_cache.clone();
+        std::thread::spawn(move || {
+            for path in todo {
+                let time = modified(&path);
+                let text = extract::receipt_text(&path, &ocr_cache).unwrap_or_else(|e| {
+                    tracing::warn!("no text for {:?}: {:#}", path, e);
+                    String::new()
+                });
+                let facts = extract::facts(&path, &text);
+                if tx.send((path, time, facts)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.pending = Some(rx);
+    }
+
+    /// returns true if new texts arrived or the scan is done
+    pub fn poll(&mut self) -> bool {
+        let Some(rx) = self.pending.as_ref() else {
+            return false;
+        };
+
+        let mut received = false;
+        loop {
+            match rx.try_recv() {
+                Ok((path, time, facts)) => {
+                    self.facts.insert(path, (time, facts));
+                    received = true;
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => break,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.pending = None;
+                    received = true;
+                    break;
+                }
+            }
+        }
+
+        received
+    }
+
+    /// lowercase text of a receipt once extracted
+    pub fn text(&self, receipt: &Path) -> Option<&str> {
+        self.facts.get(receipt).map(|(_, f)| f.text.as_str())
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    /// `transactions` are the unassigned rows with their index
+    pub fn update(&mut self, receipts: &[PathBuf], transactions: &[(usize, &Transaction)]) {
+        let mut candidates: Vec<Suggestion> = receipts
+            .iter()
+            .filter_map(|receipt| Some((receipt, &self.facts.get(receipt)?.1)))
+            .flat_map(|(receipt, facts)| {
+                transactions.iter().map(move |(row, t)| Suggestion {
+                    receipt: receipt.clone(),
+                    row: *row,
+                    confidence: confidence(facts, t),
+                })
+            })
+            .filter(|s| s.confidence >= MIN_CONFIDENCE)
+            .filter(|s| !self.rejected.contains(&(s.receipt.clone(), s.row)))
+            .collect();
+
+        candidates.sort_by(|a, b| b.confidence.total_cmp(&a.confidence));
+
+        // each receipt and row is suggested at most once, best matches first
+        let mut used_receipts = HashSet::new();
+        let mut used_rows = HashSet::new();
+        self.suggestions = candidates
+            .into_iter()
+            .filter(|s| {
+                if used_receipts.contains(&s.receipt) || used_rows.contains(&s.row) {
+                    return false;
+                }
+                used_receipts.insert(s.receipt.clone());
+                used_rows.insert(s.row);
+                true
+            })
+            .collect();
+    }
+
+    pub fn suggestion_for_row(&self, row: usize) -> Option<&Suggestion> {
+        self.suggestions.iter().find(|s| s.row == row)
+    }
+
+    pub fn suggestions(&self) -> &[Suggestion] {
+        &self.suggestions
+    }
+
+    pub fn reject(&mut self, suggestion: &Suggestion) {
+        self.rejected
+            .insert((suggestion.receipt.clone(), suggestion.row));
+        self.suggestions
+            .retain(|s| !(s.receipt == suggestion.receipt && s.row == suggestion.row));
+    }
+}
+
+fn confidence(facts: &ReceiptFacts, transaction: &Transaction) -> f32 {
+    let amount = transaction.amount.abs();
+
+    let mut score = 0.0;
+
+    if facts.amounts.contains(&amount) {
+        score += 0.5;
+    }
+
+    let days = facts
+        .dates
+        .iter()
+        .map(|d| (*d - transaction.date).num_days().abs())
+        .min();
+    score += match days {
+        Some(0) => 0.25,
+        Some(1..=3) => 0.15,
+        Some(4..=10) => 0.05,
+        _ => 0.0,
+    };
+
+    let words: Vec<String> = transaction
+        .description
+        .split(|c: char| !c.is_alphanumeric())
+        .filter(|w| w.chars().count() >= 3 && w.chars().any(char::is_alphabetic))
+        .map(str::to_lowercase)
+        .collect();
+    if !words.is_empty() {
+        let found = words.iter().filter(|w| facts.text.contains(*w)).count();
+        score += 0.25 * found as f32 / words.len() as f32;
+    }
+
+    score
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdir::TestDir;
+
+    fn scan(matcher: &mut Matcher, receipts: &[PathBuf]) {
+        matcher.scan(receipts);
+        while matcher.is_busy() {
+            matcher.poll();
+            std::thread::sleep(std::time::Duration::from_millis(1));
+        }
+    }
+
+    #[test]
+    fn replaced_receipt_is_read_again() {
+        let dir = TestDir::new("matcher");
+        let receipt = dir.join("invoice.html");
+        std::fs::write(&receipt, "<p>Total 12,34 EUR</p>").unwrap();
+        let receipts = vec![receipt.clone()];
+
+        let mut matcher = Matcher::default();
+        scan(&mut matcher, &receipts);
+        assert!(matcher.text(&receipt).unwrap().contains("12,34"));
+
+        // unchanged files are not read again
+        matcher.scan(&receipts);
+        assert!(!matcher.is_busy());
+
+        // wait for a modification time the file system can tell apart
+        let before = modified(&receipt);
+        while modified(&receipt) == before {
+            std::thread::sleep(std::time::Duration::from_millis(10));
+            std::fs::write(&receipt, "<p>Total 56,78 EUR</p>").unwrap();
+        }
+        scan(&mut matcher, &receipts);
+        let text = matcher.text(&receipt).unwrap();
+        assert!(text.contains("56,78") && !text.contains("12,34"));
+    }
+}
@@ -0,0 +1,92 @@
+use anyhow::{bail, Context, Result};
+use std::{
+    path::{Path, PathBuf},
+    process::Command,
+};
+
+use crate::{backup, duplicates::hash_file};
+
+/// optional tesseract language(s), e.g. `deu+eng`
+const LANG_ENV: &str = "CCACCOUNTING_OCR_LANG";
+const PDF_RESOLUTION: &str = "300";
+
+/// runs OCR unless the result for the same file content is cached already
+pub fn cached_text(path: &Path, cache_dir: &Path) -> Result<String> {
+    let hash = hash_file(path)?;
+    let cache_file = cache_dir.join(format!("{hash}.txt"));
+
+    if let Ok(text) = std::fs::read_to_string(&cache_file) {
+        return Ok(text);
+    }
+
+    tracing::info!("ocr: {:?}", path);
+    let text = recognize(path, &hash)?;
+
+    std::fs::create_dir_all(cache_dir)?;
+    backup::write_atomic(&cache_file, text.as_bytes())?;
+
+    Ok(text)
+}
+
+fn recognize(path: &Path, hash: &str) -> Result<String> {
+    let is_pdf = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("pdf"))
+        .unwrap_or_default();
+
+    if !is_pdf {
+        return tesseract(path);
+    }
+
+    // tesseract does not read pdfs, so render the pages to images first
+    let dir = std::env::temp_dir().join(format!("ccaccounting-ocr-{hash}"));
+    std::fs::create_dir_all(&dir)?;
+
+    let res = (|| {
+        let status = Command::new("pdftoppm")
+            .args(["-r", PDF_RESOLUTION, "-png"])
+            .arg(path)
+            .arg(dir.join("page"))
+            .status()
+            .context("running pdftoppm")?;
+        if !status.success() {
+            bail!("pdftoppm failed: {}", status);
+        }
+
+        let mut pages: Vec<PathBuf> = std::fs::read_dir(&dir)?
+            .filter_map(|e| e.ok())
+            .map(|e| e.path())
+            .collect();
+        pages.sort();
+
+        pages
+            .iter()
+            .map(|page| tesseract(page))
+            .collect::<Result<Vec<_>>>()
+            .map(|pages| pages.join("\n"))
+    })();
+
+    if let Err(e) = std::fs::remove_dir_all(&dir) {
+        tracing::warn!("removing ocr temp dir failed: {}", e);
+    }
+
+    res
+}
+
+fn tesseract(image: &Path) -> Result<String> {
+    let mut cmd = Command::new("tesseract");
+    cmd.arg(image).arg("stdout");
+    if let Ok(lang) = std::env::var(LANG_ENV) {
+        cmd.args(["-l", &lang]);
+    }
+
+    let output = cmd.output().context("running tesseract")?;
+    if !output.status.success() {
+        bail!(
+            "tesseract failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
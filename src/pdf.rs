@@ -0,0 +1,360 @@
+use anyhow::{bail, Result};
+use image::{codecs::jpeg::JpegDecoder, ColorType, ImageDecoder};
+use lopdf::{
+    content::{Content, Operation},
+    dictionary, Dictionary, Document, Object, ObjectId, Stream,
+};
+use std::{fs::File, io::BufReader, path::Path};
+
+use crate::backup;
+
+/// a4 in points
+const PAGE_SIZE: (f32, f32) = (595.0, 842.0);
+const PAGE_MARGIN: f32 = 20.0;
+
+/// writes a single page pdf showing the image scaled to fit an a4 page
+pub fn image_to_pdf(image: &Path, target: &Path) -> Result<()> {
+    if target.exists() {
+        bail!("'{}' already exists", target.display());
+    }
+
+    let mut writer = PdfWriter::new();
+    writer.add_image(image)?;
+    writer.save(target)
+}
+
+/// a line of text on a page, in points
+pub struct TextLine {
+    pub text: String,
+    pub size: f32,
+}
+
+impl TextLine {
+    pub fn new(text: impl Into<String>, size: f32) -> Self {
+        Self {
+            text: text.into(),
+            size,
+        }
+    }
+
+    /// the text split at spaces into lines that fit the width of a page, blank lines are dropped
+    pub fn wrapped(text: &str, size: f32) -> Vec<Self> {
+        let max_chars = max_chars(size);
+        let mut res = Vec::new();
+
+        for paragraph in text.lines().filter(|l| !l.trim().is_empty()) {
+            let mut line = String::new();
+            for word in paragraph.split_whitespace() {
+                if !line.is_empty() && line.chars().count() + 1 + word.chars().count() > max_chars {
+                    res.push(Self::new(std::mem::take(&mut line), size));
+                }
+                if !line.is_empty() {
+                    line.push(' ');
+                }
+                line.push_str(word);
+            }
+            res.push(Self::new(line, size));
+        }
+
+        res
+    }
+}
+
+/// characters of a line at the font size that fit between the margins
+fn max_chars(size: f32) -> usize {
+    ((PAGE_SIZE.0 - 4.0 * PAGE_MARGIN) / (size * 0.6)) as usize
+}
+
+/// builds a pdf page by page
+pub struct PdfWriter {
+    doc: Document,
+    pages_id: ObjectId,
+    font_id: ObjectId,
+    pages: Vec<ObjectId>,
+}
+
+impl Default for PdfWriter {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl PdfWriter {
+    pub fn new() -> Self {
+        let mut doc = Document::with_version("1.5");
+        let pages_id = doc.new_object_id();
+        // a standard font every viewer has, so nothing needs embedding,
+        // monospaced to line up the columns of listings
+        let font_id = doc.add_object(dictionary! {
+            "Type" => "Font",
+            "Subtype" => "Type1",
+            "BaseFont" => "Courier",
+            "Encoding" => "WinAnsiEncoding",
+        });
+
+        Self {
+            doc,
+            pages_id,
+            font_id,
+            pages: Vec::new(),
+        }
+    }
+
+    pub fn page_count(&self) -> usize {
+        self.pages.len()
+    }
+
+    /// the image scaled to fit an a4 page
+    pub fn add_image(&mut self, image: &Path) -> Result<()> {
+        let (image, width, height) = image_stream(image)?;
+        let image_id = self.doc.add_object(image);
+
+        let (page_width, page_height) = PAGE_SIZE;
+        let scale = ((page_width - 2.0 * PAGE_MARGIN) / width as f32)
+            .min((page_height - 2.0 * PAGE_MARGIN) / height as f32);
+        let (w, h) = (width as f32 * scale, height as f32 * scale);
+
+        let content = Content {
+            operations: vec![
+                Operation::new("q", vec![]),
+                Operation::new(
+                    "cm",
+                    vec![
+                        w.into(),
+                        0.into(),
+                        0.into(),
+                        h.into(),
+                        ((page_width - w) / 2.0).into(),
+                        ((page_height - h) / 2.0).into(),
+                    ],
+                ),
+                Operation::new("Do", vec![Object::Name(b"Im0".to_vec())]),
+                Operation::new("Q", vec![]),
+            ],
+        };
+
+        self.add_page(
+            content,
+            dictionary! {
+                "XObject" => dictionary! { "Im0" => image_id },
+            },
+        )
+    }
+
+    /// lines from the top of the page on, continued on further pages when they do not fit
+    pub fn add_text(&mut self, lines: &[TextLine]) -> Result<()> {
+        let page_height = PAGE_SIZE.1;
+        let margin = 2.0 * PAGE_MARGIN;
+
+        let mut lines = lines.iter().peekable();
+        while lines.peek().is_some() {
+            let mut operations = Vec::new();
+            let mut y = page_height - margin;
+
+            while let Some(line) = lines.next_if(|l| y - l.size * 1.4 >= margin) {
+                y -= line.size * 1.4;
+
+                operations.extend([
+                    Operation::new("BT", vec![]),
+                    Operation::new("Tf", vec!["F1".into(), line.size.into()]),
+                    Operation::new("Td", vec![margin.into(), y.into()]),
+                    Operation::new(
+                        "Tj",
+                        vec![Object::string_literal(win_ansi(
+                            &line.text,
+                            max_chars(line.size),
+                        ))],
+                    ),
+                    Operation::new("ET", vec![]),
+                ]);
+            }
+
+            if operations.is_empty() {
+                bail!("text does not fit on a page");
+            }
+
+            self.add_page(
+                Content { operations },
+                dictionary! {
+                    "Font" => dictionary! { "F1" => self.font_id },
+                },
+            )?;
+        }
+
+        Ok(())
+    }
+
+    /// appends all pages of the pdf, they keep their own size and resources
+    pub fn add_pdf(&mut self, path: &Path) -> Result<()> {
+        let mut other = Document::load(path)?;
+        if other.is_encrypted() {
+            bail!("'{}' is encrypted", path.display());
+        }
+
+        other.renumber_objects_with(self.doc.max_id + 1);
+        let pages: Vec<ObjectId> = other.get_pages().into_values().collect();
+        if pages.is_empty() {
+            bail!("'{}' has no pages", path.display());
+        }
+
+        for page_id in &pages {
+            let inherited = inherited_attributes(&other, *page_id);
+            let page = other.get_object_mut(*page_id)?.as_dict_mut()?;
+            for (key, value) in inherited {
+                page.set(key, value);
+            }
+            page.set("Parent", self.pages_id);
+        }
+
+        // the page tree of the other document is replaced by ours
+        for (id, object) in other.objects {
+            let is_tree = object
+                .as_dict()
+                .and_then(|d| d.get(b"Type"))
+                .and_then(Object::as_name)
+                .is_ok_and(|t| t == b"Pages" || t == b"Catalog");
+            if !is_tree {
+                self.doc.objects.insert(id, object);
+            }
+        }
+        self.doc.max_id = self.doc.max_id.max(other.max_id);
+        self.pages.extend(pages);
+
+        Ok(())
+    }
+
+    fn add_page(&mut self, content: Content, resources: Dictionary) -> Result<()> {
+        let (page_width, page_height) = PAGE_SIZE;
+        let content_id = self
+            .doc
+            .add_object(Stream::new(dictionary! {}, content.encode()?));
+        let page_id = self.doc.add_object(dictionary! {
+            "Type" => "Page",
+            "Parent" => self.pages_id,
+            "Contents" => content_id,
+            "Resources" => resources,
+            "MediaBox" => vec![0.into(), 0.into(), page_width.into(), page_height.into()],
+        });
+        self.pages.push(page_id);
+        Ok(())
+    }
+
+    pub fn save(mut self, target: &Path) -> Result<()> {
+        let count = self.pages.len() as i64;
+        let kids = self.pages.into_iter().map(Object::from).collect::<Vec<_>>();
+        self.doc.objects.insert(
+            self.pages_id,
+            Object::Dictionary(dictionary! {
+                "Type" => "Pages",
+                "Kids" => kids,
+                "Count" => count,
+            }),
+        );
+        let catalog_id = self.doc.add_object(dictionary! {
+            "Type" => "Catalog",
+            "Pages" => self.pages_id,
+        });
+        self.doc.trailer.set("Root", catalog_id);
+
+        let mut content = Vec::new();
+        self.doc.save_to(&mut content)?;
+        backup::write_atomic(target, &content)
+    }
+}
+
+/// attributes a page takes from its ancestors in the page tree
+fn inherited_attributes(doc: &Document, page_id: ObjectId) -> Vec<(Vec<u8>, Object)> {
+    let mut res = Vec::<(Vec<u8>, Object)>::new();
+    let Ok(page) = doc.get_dictionary(page_id) else {
+        return res;
+    };
+
+    let mut parent = page.get(b"Parent").and_then(Object::as_reference).ok();
+    // a broken tree could point back at itself
+    for _ in 0..32 {
+        let Some(node) = parent.and_then(|id| doc.get_dictionary(id).ok()) else {
+            break;
+        };
+        for key in [&b"Resources"[..], b"MediaBox", b"CropBox", b"Rotate"] {
+            if !page.has(key) && !res.iter().any(|(k, _)| k == key) {
+                if let Ok(value) = node.get(key) {
+                    res.push((key.to_vec(), value.clone()));
+                }
+            }
+        }
+        parent = node.get(b"Parent").and_then(Object::as_reference).ok();
+    }
+
+    res
+}
+
+/// the standard fonts only know latin characters, everything else becomes `?`
+fn win_ansi(text: &str, max_chars: usize) -> Vec<u8> {
+    let mut res: Vec<u8> = text
+        .chars()
+        .filter(|c| !c.is_control())
+        .map(|c| match c {
+            '€' => 0x80,
+            '–' => 0x96,
+            '—' => 0x97,
+            c if (c as u32) < 0x80 || (0xa0..=0xff).contains(&(c as u32)) => c as u8,
+            _ => b'?',
+        })
+        .collect();
+
+    if res.len() > max_chars {
+        res.truncate(max_chars.saturating_sub(3));
+        res.extend(b"...");
+    }
+    res
+}
+
+/// jpegs are embedded as they are, everything else is stored as compressed rgb
+fn image_stream(path: &Path) -> Result<(Stream, u32, u32)> {
+    let is_jpeg = path
+        .extension()
+        .map(|ext| ext.eq_ignore_ascii_case("jpg") || ext.eq_ignore_ascii_case("jpeg"))
+        .unwrap_or_default();
+
+    if is_jpeg {
+        let decoder = JpegDecoder::new(BufReader::new(File::open(path)?))?;
+        let (width, height) = decoder.dimensions();
+        let color_space = match decoder.color_type() {
+            ColorType::L8 => "DeviceGray",
+            ColorType::Rgb8 => "DeviceRGB",
+            other => bail!("unsupported jpeg color type: {:?}", other),
+        };
+
+        let stream = Stream::new(
+            dictionary! {
+                "Type" => "XObject",
+                "Subtype" => "Image",
+                "Width" => width,
+                "Height" => height,
+                "ColorSpace" => color_space,
+                "BitsPerComponent" => 8,
+                "Filter" => "DCTDecode",
+            },
+            std::fs::read(path)?,
+        );
+        return Ok((stream, width, height));
+    }
+
+    let image = image::open(path)?.to_rgb8();
+    let (width, height) = image.dimensions();
+
+    let mut stream = Stream::new(
+        dictionary! {
+            "Type" => "XObject",
+            "Subtype" => "Image",
+            "Width" => width,
+            "Height" => height,
+            "ColorSpace" => "DeviceRGB",
+            "BitsPerComponent" => 8,
+        },
+        image.into_raw(),
+    );
+    stream.compress()?;
+
+    Ok((stream, width, height))
+}
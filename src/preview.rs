@@ -0,0 +1,278 @@
+use anyhow::{bail, Context, Result};
+use egui::{Color32, ColorImage, TextureHandle, TextureOptions, Ui};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    process::Command,
+    sync::mpsc::{channel, Receiver},
+};
+
+use crate::receipttype::ReceiptKind;
+
+const PDF_RESOLUTION: &str = "150";
+const MIN_ZOOM: f32 = 0.25;
+const MAX_ZOOM: f32 = 4.0;
+/// rendered pages kept before the cache is cleared
+const MAX_CACHED_PAGES: usize = 32;
+
+type Page = (PathBuf, usize);
+
+/// rendered page and the number of pages in the file
+type Rendered = Result<(ColorImage, usize), String>;
+
+/// shows the selected or hovered receipt inside the app
+pub struct Preview {
+    pub open: bool,
+    selected: Option<PathBuf>,
+    /// hovered receipt of the last frame, takes precedence over the selection
+    hovered: Option<PathBuf>,
+    hovered_next: Option<PathBuf>,
+    /// page per file, zero based
+    pages: HashMap<PathBuf, usize>,
+    page_counts: HashMap<PathBuf, usize>,
+    /// `1.0` fits the page to the panel width
+    zoom: f32,
+    textures: HashMap<Page, std::result::Result<TextureHandle, String>>,
+    pending: Option<(Page, Receiver<Rendered>)>,
+}
+
+impl Default for Preview {
+    fn default() -> Self {
+        Self {
+            open: true,
+            selected: None,
+            hovered: None,
+            hovered_next: None,
+            pages: HashMap::new(),
+            page_counts: HashMap::new(),
+            zoom: 1.0,
+            textures: HashMap::new(),
+            pending: None,
+        }
+    }
+}
+
+impl std::fmt::Debug for Preview {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Preview")
+            .field("open", &self.open)
+            .field("selected", &self.selected)
+            .finish()
+    }
+}
+
+impl Preview {
+    pub fn select(&mut self, path: &Path) {
+        self.selected = Some(path.to_path_buf());
+    }
+
+    /// to be called every frame the receipt is hovered
+    pub fn hover(&mut self, path: &Path) {
+        self.hovered_next = Some(path.to_path_buf());
+    }
+
+    fn current(&self) -> Option<&PathBuf> {
+        self.hovered.as_ref().or(self.selected.as_ref())
+    }
+
+    pub fn show(&mut self, ui: &mut Ui) {
+        self.hovered = self.hovered_next.take();
+
+        if !self.open {
+            return;
+        }
+
+        let ctx = ui.ctx().clone();
+        self.poll(&ctx);
+
+        egui::SidePanel::right("preview_panel")
+            .default_width(300.0)
+            .show_inside(ui, |ui| {
+                ui.vertical_centered(|ui| {
+                    ui.heading("Preview");
+                });
+
+                let Some(path) = self.current().cloned() else {
+                    ui.label("select or hover a receipt");
+                    return;
+                };
+
+                ui.label(
+                    path.file_name()
+                        .map(|f| f.to_string_lossy().to_string())
+                        .unwrap_or_default(),
+                );
+
+                match ReceiptKind::from_path(&path) {
+                    Some(ReceiptKind::Pdf | ReceiptKind::Image) => self.draw_page(ui, &path),
+                    Some(kind) => {
+                        ui.label(format!(
+                            "{} no preview for {} receipts",
+                            kind.icon(),
+                            kind.name()
+                        ));
+                    }
+                    None => {
+                        ui.label("no preview");
+                    }
+                }
+            });
+
+        if self.pending.is_some() {
+            ctx.request_repaint_after(std::time::Duration::from_millis(100));
+        }
+    }
+
+    fn draw_page(&mut self, ui: &mut Ui, path: &Path) {
+        let page = self.pages.get(path).copied().unwrap_or_default();
+        let page_count = self.page_counts.get(path).copied();
+
+        ui.horizontal(|ui| {
+            if ui
+                .add_enabled(page > 0, egui::Button::new("◀"))
+                .on_hover_text("previous page")
+                .clicked()
+            {
+                self.pages.insert(path.to_path_buf(), page - 1);
+            }
+            match page_count {
+                Some(count) => ui.label(format!("{}/{}", page + 1, count)),
+                None => ui.label(format!("{}", page + 1)),
+            };
+            if ui
+                .add_enabled(
+                    page_count.is_some_and(|count| page + 1 < count),
+                    egui::Button::new("▶"),
+                )
+                .on_hover_text("next page")
+                .clicked()
+            {
+                self.pages.insert(path.to_path_buf(), page + 1);
+            }
+
+            ui.separator();
+
+            if ui.small_button("-").on_hover_text("zoom out").clicked() {
+                self.zoom = (self.zoom / 1.25).max(MIN_ZOOM);
+            }
+            ui.label(format!("{:.0}%", self.zoom * 100.0));
+            if ui.small_button("+").on_hover_text("zoom in").clicked() {
+                self.zoom = (self.zoom * 1.25).min(MAX_ZOOM);
+            }
+            if ui.small_button("fit").clicked() {
+                self.zoom = 1.0;
+            }
+        });
+
+        let key = (
+            path.to_path_buf(),
+            self.pages.get(path).copied().unwrap_or_default(),
+        );
+        let zoom = self.zoom;
+
+        match self.textures.get(&key) {
+            Some(Ok(texture)) => {
+                let size = texture.size_vec2();
+                let width = ui.available_width() * zoom;
+                egui::ScrollArea::both().show(ui, |ui| {
+                    ui.image(texture, size * (width / size.x));
+                });
+            }
+            Some(Err(e)) => {
+                ui.colored_label(Color32::RED, e);
+            }
+            None => {
+                ui.spinner();
+                self.render(key);
+            }
+        }
+    }
+
+    fn render(&mut self, page: Page) {
+        if self.pending.is_some() {
+            return;
+        }
+
+        let (tx, rx) = channel();
+        let (path, index) = page.clone();
+        std::thread::spawn(move || {
+            let rendered = render_page(&path, index).map_err(|e| {
+                tracing::warn!("preview of {:?} failed: {:#}", path, e);
+                format!("{e:#}")
+            });
+            tx.send(rendered).ok();
+        });
+
+        self.pending = Some((page, rx));
+    }
+
+    fn poll(&mut self, ctx: &egui::Context) {
+        let Some((_, rx)) = self.pending.as_ref() else {
+            return;
+        };
+
+        let rendered = match rx.try_recv() {
+            Ok(rendered) => rendered,
+            Err(std::sync::mpsc::TryRecvError::Empty) => return,
+            Err(std::sync::mpsc::TryRecvError::Disconnected) => Err("rendering failed".into()),
+        };
+        let Some((page, _)) = self.pending.take() else {
+            return;
+        };
+
+        if self.textures.len() >= MAX_CACHED_PAGES {
+            self.textures.clear();
+        }
+
+        let texture = rendered.map(|(image, count)| {
+            self.page_counts.insert(page.0.clone(), count);
+            ctx.load_texture(
+                format!("preview {} {}", page.0.display(), page.1),
+                image,
+                TextureOptions::default(),
+            )
+        });
+        self.textures.insert(page, texture);
+    }
+
+    /// rendered pages are outdated once the file changed
+    pub fn invalidate(&mut self, path: &Path) {
+        self.textures.retain(|(p, _), _| p != path);
+        self.page_counts.remove(path);
+    }
+}
+
+fn render_page(path: &Path, page: usize) -> Result<(ColorImage, usize)> {
+    if ReceiptKind::from_path(path) == Some(ReceiptKind::Image) {
+        let image = image::open(path)?.to_rgba8();
+        let size = [image.width() as usize, image.height() as usize];
+        return Ok((ColorImage::from_rgba_unmultiplied(size, image.as_raw()), 1));
+    }
+
+    let page_count = lopdf::Document::load(path)
+        .map(|doc| doc.get_pages().len())
+        .unwrap_or(1);
+
+    // pdftoppm pages are one based
+    let page = (page + 1).to_string();
+    let output = Command::new("pdftoppm")
+        .args(["-f", &page, "-l", &page, "-r", PDF_RESOLUTION, "-png"])
+        .arg(path)
+        .output()
+        .context("running pdftoppm (poppler) for the pdf preview")?;
+    if !output.status.success() {
+        bail!(
+            "pdftoppm failed: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let image =
+        image::load_from_memory_with_format(&output.stdout, image::ImageFormat::Png)?.to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+
+    Ok((
+        ColorImage::from_rgba_unmultiplied(size, image.as_raw()),
+        page_count,
+    ))
+}
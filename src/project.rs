@@ -2,56 +2,206 @@ use anyhow::Result;
 use eframe::epaint;
 use egui::{
     ecolor, Color32, CursorIcon, Id, InnerResponse, KeyboardShortcut, Label, LayerId, Modifiers,
-    Order, PointerButton, Rect, Response, Sense, Shape, Ui, Vec2, WidgetText,
+    Order, PointerButton, Rect, Response, RichText, Sense, Shape, Ui, Vec2, WidgetText,
 };
+use egui_extras::TableRow;
 use ron::ser::PrettyConfig;
 use std::{
+    collections::{BTreeSet, HashMap, HashSet},
     fs::File,
-    io::{BufReader, BufWriter},
+    io::BufReader,
     path::{Path, PathBuf},
 };
 
-use crate::rowmetadata::RowMetaData;
+use crate::{
+    backup::{self, Backup},
+    discovery::ReceiptSources,
+    duplicates::Hashes,
+    export::{ExportFormat, PackageExport, PackageRow},
+    filter::{CellOrder, RowStatus, Sort, SortColumn, StatusFilter, TableFilter},
+    format::{self, ColumnMapping, FormatKind, MilesAndMore, StatementFormat},
+    history::{Command, History},
+    mappingwizard::MappingWizard,
+    matcher::{Matcher, Suggestion},
+    preview::Preview,
+    receipttype::ReceiptKind,
+    reconcile::{Balances, ReconcileWindow, Reconciliation},
+    rename::{RenameDialog, RenameIssue, RenameOp},
+    rowmetadata::{RowMetaData, SplitStatus},
+    settings::{ProjectSettings, Settings},
+    state::{self, StateData},
+    summary::{Summary, Totals},
+    tablelayout::TableLayout,
+    template::{self, TemplateContext},
+    thumbnail::Thumbnails,
+    transaction::{self, Transaction},
+    watcher::ReceiptWatcher,
+};
 
-#[derive(Debug, Default, serde::Deserialize)]
+#[derive(Debug, Clone, Default, serde::Deserialize)]
 #[serde(default)]
 pub struct CsvRow {
     pub cells: Vec<String>,
 }
 
-#[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
-pub struct StateData {
-    pub show_hidden: bool,
-    pub row_meta_data: Vec<RowMetaData>,
+impl CsvRow {
+    pub fn cell(&self, column: Option<usize>) -> &str {
+        column
+            .and_then(|c| self.cells.get(c))
+            .map(String::as_str)
+            .unwrap_or_default()
+    }
+}
+
+const THUMBNAIL_HEIGHT: f32 = 32.0;
+const ROW_HEIGHT: f32 = 18.0;
+const DEFAULT_COLUMN_WIDTH: f32 = 80.0;
+
+/// what the table needs while drawing and what was done in it, applied afterwards
+struct TableFrame {
+    contains_pointer: bool,
+    template: String,
+    convert_images: bool,
+    converted_dir: PathBuf,
+    categories: Vec<String>,
+    /// target names of shared receipts
+    shared_targets: HashMap<String, String>,
+    sort: Option<Sort>,
+    column_names: Vec<String>,
+    visible_columns: Vec<usize>,
+    layout: TableLayout,
+    command: Option<Command>,
+    rejected: Option<Suggestion>,
+    preview_hovered: Option<PathBuf>,
+    preview_selected: Option<PathBuf>,
+    sort_clicked: Option<SortColumn>,
 }
 
 #[derive(Debug)]
 pub struct Project {
     state: StateData,
+    load_error: Option<String>,
+    save_blocked: bool,
+    /// a copy of the unreadable state file was kept
+    state_copied: bool,
+    backups: Option<Vec<Backup>>,
+    history: History,
+    rename_dialog: RenameDialog,
+    matcher: Matcher,
+    thumbnails: Thumbnails,
+    hashes: Hashes,
+    /// groups of identical unassigned receipts by each of their paths, indices into `receipts`
+    duplicates: HashMap<PathBuf, Vec<usize>>,
+    preview: Preview,
+    file_filter: String,
+    error: Option<String>,
+    /// result of a finished background task
+    notice: Option<String>,
+    package_export: PackageExport,
+    format: Box<dyn StatementFormat>,
+    columns: ColumnMapping,
+    mapping_wizard: MappingWizard,
+    settings: Settings,
+    category_input: String,
+    amount_input: String,
+    reason_input: String,
 
     input_file: PathBuf,
+    /// column titles, if the csv has a header row
+    header: Option<CsvRow>,
     rows: Vec<CsvRow>,
+    transactions: Vec<Result<Transaction>>,
+    row_ids: Vec<String>,
+    orphans: Vec<String>,
     visible_rows: Vec<usize>,
-    pdfs: Vec<PathBuf>,
+    table_filter: TableFilter,
+    sort: Option<Sort>,
+    summary: Summary,
+    reconciliation: Reconciliation,
+    reconcile_window: ReconcileWindow,
+    /// balances listed in the csv above the header
+    statement_balances: Balances,
+    /// unassigned receipts
+    receipts: Vec<PathBuf>,
+    /// all receipts in the folders as of the last walk, kept up to date by the watcher
+    found_receipts: Vec<PathBuf>,
+    sources: ReceiptSources,
+    /// set when the receipt folders changed and need to be watched
+    watch_pending: bool,
+    watcher: Option<ReceiptWatcher>,
+    unreadable: Vec<String>,
+    /// assigned receipts that are not on disk anymore
+    missing_receipts: HashSet<String>,
+    /// receipts assigned to more than one row, with these rows
+    shared_receipts: HashMap<String, Vec<usize>>,
     max_cells: usize,
     drop_row: Option<usize>,
-    drag_row: Option<usize>,
+    drag_receipt: Option<PathBuf>,
+    /// drop onto a row that waits for confirmation: row, receipt and the rows with identical files
+    pending_drop: Option<(usize, String, Vec<String>)>,
 
     shortcut_reread_files: KeyboardShortcut,
+    shortcut_undo: KeyboardShortcut,
+    shortcut_redo: KeyboardShortcut,
 }
 
 impl Default for Project {
     fn default() -> Self {
         Self {
             shortcut_reread_files: egui::KeyboardShortcut::new(Modifiers::COMMAND, egui::Key::R),
+            shortcut_undo: egui::KeyboardShortcut::new(Modifiers::COMMAND, egui::Key::Z),
+            shortcut_redo: egui::KeyboardShortcut::new(
+                Modifiers::COMMAND.plus(Modifiers::SHIFT),
+                egui::Key::Z,
+            ),
             state: Default::default(),
+            load_error: Default::default(),
+            save_blocked: Default::default(),
+            state_copied: Default::default(),
+            backups: Default::default(),
+            history: Default::default(),
+            rename_dialog: Default::default(),
+            matcher: Default::default(),
+            thumbnails: Default::default(),
+            hashes: Default::default(),
+            duplicates: Default::default(),
+            preview: Default::default(),
+            file_filter: Default::default(),
+            error: Default::default(),
+            notice: Default::default(),
+            package_export: Default::default(),
+            format: Box::new(MilesAndMore),
+            columns: Default::default(),
+            mapping_wizard: Default::default(),
+            settings: Default::default(),
+            category_input: Default::default(),
+            amount_input: Default::default(),
+            reason_input: Default::default(),
             input_file: Default::default(),
+            header: Default::default(),
             rows: Default::default(),
-            pdfs: Default::default(),
+            transactions: Default::default(),
+            row_ids: Default::default(),
+            orphans: Default::default(),
+            receipts: Default::default(),
+            found_receipts: Default::default(),
+            sources: Default::default(),
+            watch_pending: Default::default(),
+            watcher: Default::default(),
+            unreadable: Default::default(),
+            missing_receipts: Default::default(),
+            shared_receipts: Default::default(),
             visible_rows: Default::default(),
+            table_filter: Default::default(),
+            sort: Default::default(),
+            summary: Default::default(),
+            reconciliation: Default::default(),
+            reconcile_window: Default::default(),
+            statement_balances: Default::default(),
             max_cells: Default::default(),
             drop_row: Default::default(),
-            drag_row: Default::default(),
+            drag_receipt: Default::default(),
+            pending_drop: Default::default(),
         }
     }
 }
@@ -60,89 +210,698 @@ impl Project {
     pub fn new(input: PathBuf) -> Result<Self> {
         assert_eq!(input.extension().unwrap(), "csv");
 
-        let file = std::fs::File::open(&input)?;
-        let mut rdr = csv::ReaderBuilder::new()
-            .flexible(true)
-            .delimiter(b';')
-            .from_reader(file);
-        let mut rows = Vec::new();
-        let mut max_cells = 0;
-        for result in rdr.byte_records() {
-            let result = result?;
-            let mut row = Vec::new();
-            for result in result.iter() {
-                row.push(String::from_utf8_lossy(result).to_string());
-            }
-
-            max_cells = std::cmp::max(max_cells, row.len());
-
-            rows.push(CsvRow { cells: row });
-        }
+        let content = std::fs::read(&input)?;
 
         let mut project = Self {
-            rows,
-            max_cells,
             input_file: input,
-            pdfs: Vec::new(),
+            receipts: Vec::new(),
             ..Default::default()
         };
 
         project.load()?;
+        project.matcher = Matcher::new(project.ocr_cache_dir()?);
+
+        let kind = *project
+            .state
+            .format
+            .get_or_insert_with(|| format::detect(&content));
+        project.format = kind.format();
+
+        tracing::info!("statement format: {}", project.format.name());
+
+        let records = format::read_records(&content, project.format.delimiter(), None)?;
+        let preamble = project.format.header_rows().saturating_sub(1);
+        project.statement_balances =
+            Balances::from_preamble(&records[..preamble.min(records.len())]);
+        (project.header, project.rows) = format::split_header(project.format.as_ref(), records);
+        project.max_cells = project
+            .rows
+            .iter()
+            .map(|row| row.cells.len())
+            .max()
+            .unwrap_or_default();
+        project.state.table_layout.retain_columns(project.max_cells);
+
+        project.columns = match project.load_mapping()? {
+            Some(mapping) => mapping,
+            None => {
+                let columns = project.format.columns();
+                if matches!(kind, FormatKind::Generic { .. }) {
+                    project.mapping_wizard.open(columns);
+                }
+                columns
+            }
+        };
+
+        project.update_transactions();
+        project.migrate_indexed_meta_data();
 
-        project.reread_pdfs();
+        project.update_sources();
+        project.reread_files();
 
-        project.update_hidden();
+        project.update_visible_rows();
+        project.update_summary();
 
         Ok(project)
     }
 
-    fn update_hidden(&mut self) {
-        self.visible_rows = self
+    fn update_transactions(&mut self) {
+        self.transactions = self
+            .rows
+            .iter()
+            .map(|row| Transaction::from_row(row, &self.columns, self.format.as_ref()))
+            .collect();
+
+        let errors = self.transactions.iter().filter(|t| t.is_err()).count();
+        if errors > 0 {
+            tracing::warn!("rows failed to parse: {}", errors);
+        }
+
+        self.update_row_ids();
+    }
+
+    /// the same transaction can occur multiple times, so a counter is added per occurrence
+    fn update_row_ids(&mut self) {
+        let mut occurrences = HashMap::<String, usize>::new();
+        let row_ids: Vec<String> = self
+            .rows
+            .iter()
+            .zip(self.transactions.iter())
+            .map(|(row, t)| {
+                let fingerprint = transaction::fingerprint(row, t.as_ref().ok());
+                let count = occurrences.entry(fingerprint.clone()).or_default();
+                *count += 1;
+                format!("{}#{}", fingerprint, *count - 1)
+            })
+            .collect();
+
+        // keep meta data when the fingerprint changes (e.g. new column mapping)
+        if self.row_ids.len() == row_ids.len() {
+            let changed: Vec<(&String, &String)> = self
+                .row_ids
+                .iter()
+                .zip(row_ids.iter())
+                .filter(|(old, new)| old != new)
+                .collect();
+            let moving_away: HashSet<&String> = changed.iter().map(|(old, _)| *old).collect();
+
+            // receipts already kept under the new id win, the old meta data stays as an orphan
+            let changed: Vec<(&String, &String)> = changed
+                .into_iter()
+                .filter(|(old, new)| {
+                    let taken = !moving_away.contains(new)
+                        && self
+                            .state
+                            .rows
+                            .get(*new)
+                            .is_some_and(RowMetaData::has_receipts);
+                    if taken {
+                        tracing::warn!("not moving '{}' to '{}', it has receipts", old, new);
+                    }
+                    !taken
+                })
+                .collect();
+
+            let moved: Vec<_> = changed
+                .into_iter()
+                .filter_map(|(old, new)| self.state.rows.remove(old).map(|m| (new.clone(), m)))
+                .collect();
+            self.state.rows.extend(moved);
+        }
+
+        for id in &row_ids {
+            self.state.rows.entry(id.clone()).or_default();
+        }
+
+        self.row_ids = row_ids;
+        self.update_orphans();
+    }
+
+    fn update_orphans(&mut self) {
+        self.orphans = self
             .state
-            .row_meta_data
+            .rows
             .iter()
+            .filter(|(id, meta)| meta.has_receipts() && !self.row_ids.contains(id))
+            .map(|(id, _)| id.clone())
+            .collect();
+    }
+
+    /// state files used to store meta data by csv line
+    fn migrate_indexed_meta_data(&mut self) {
+        if self.state.row_meta_data.is_empty() {
+            return;
+        }
+
+        tracing::info!("migrate {} indexed rows", self.state.row_meta_data.len());
+
+        for (idx, meta) in std::mem::take(&mut self.state.row_meta_data)
+            .into_iter()
             .enumerate()
-            .filter(|(_, r)| !r.hidden)
-            .map(|(i, _)| i)
+        {
+            let id = self
+                .row_ids
+                .get(idx)
+                .cloned()
+                .unwrap_or_else(|| format!("line {idx}"));
+            self.state.rows.insert(id, meta);
+        }
+
+        self.update_orphans();
+    }
+
+    fn meta(&self, row_index: usize) -> Option<&RowMetaData> {
+        self.row_ids
+            .get(row_index)
+            .and_then(|id| self.state.rows.get(id))
+    }
+
+    fn assigned_rows(&self) -> impl Iterator<Item = &RowMetaData> {
+        self.row_ids.iter().filter_map(|id| self.state.rows.get(id))
+    }
+
+    fn execute(&mut self, cmd: Command) {
+        if let Err(e) = self.history.execute(cmd, &mut self.state.rows) {
+            tracing::error!("command error: {:#}", e);
+            self.error = Some(format!("{e:#}"));
+        }
+        self.after_edit();
+    }
+
+    fn undo(&mut self) {
+        if let Err(e) = self.history.undo(&mut self.state.rows) {
+            tracing::error!("undo error: {:#}", e);
+            self.error = Some(format!("undo failed: {e:#}"));
+        }
+        self.after_edit();
+    }
+
+    fn redo(&mut self) {
+        if let Err(e) = self.history.redo(&mut self.state.rows) {
+            tracing::error!("redo error: {:#}", e);
+            self.error = Some(format!("redo failed: {e:#}"));
+        }
+        self.after_edit();
+    }
+
+    fn open_rename_all(&mut self) {
+        let template = self.state.name_template();
+        let convert_images = self.state.convert_images;
+        let mut ops = Vec::new();
+        for (idx, row_id) in self.row_ids.iter().enumerate() {
+            let (Ok(transaction), Some(meta)) = (&self.transactions[idx], self.meta(idx)) else {
+                continue;
+            };
+
+            for (part, receipt) in meta.receipts.iter().enumerate() {
+                // shared receipts are renamed once, by the first row
+                if self
+                    .shared_receipts
+                    .get(&receipt.path)
+                    .is_some_and(|rows| rows.first() != Some(&idx))
+                {
+                    continue;
+                }
+
+                let Some(to) =
+                    meta.target_file_name(part, idx, transaction, template, convert_images)
+                else {
+                    continue;
+                };
+
+                let mut op = RenameOp::new(row_id.clone(), idx, receipt.path.clone(), to);
+                let invalid = meta.invalid_target_chars(part, idx, transaction, template);
+                if !invalid.is_empty() {
+                    op.issues.push(RenameIssue::InvalidCharacters(invalid));
+                }
+                ops.push(op);
+            }
+        }
+
+        match self.converted_dir() {
+            Ok(dir) => self.rename_dialog.open(ops, dir),
+            Err(e) => tracing::error!("converted dir error: {}", e),
+        }
+    }
+
+    fn after_edit(&mut self) {
+        self.update_orphans();
+        self.update_receipts();
+        self.update_visible_rows();
+        self.update_summary();
+    }
+
+    /// file name a receipt gets when renamed, shared receipts are named after their first row
+    fn target_name(&self, idx: usize, part: usize) -> Option<String> {
+        let receipt = self.meta(idx)?.receipts.get(part)?;
+        let (owner, owner_part) = match self
+            .shared_receipts
+            .get(&receipt.path)
+            .and_then(|rows| rows.first())
+        {
+            Some(owner) => (
+                *owner,
+                self.meta(*owner)?
+                    .receipts
+                    .iter()
+                    .position(|r| r.path == receipt.path)?,
+            ),
+            None => (idx, part),
+        };
+
+        let target = self.meta(owner)?.target_file_name(
+            owner_part,
+            owner,
+            self.transactions[owner].as_ref().ok()?,
+            self.state.name_template(),
+            self.state.convert_images,
+        )?;
+        Path::new(&target)
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+    }
+
+    fn start_export(&mut self, format: ExportFormat) {
+        let rows = (0..self.rows.len())
+            .map(|idx| {
+                let meta = self.meta(idx);
+                let receipts = meta
+                    .map(|m| m.receipts.as_slice())
+                    .unwrap_or_default()
+                    .iter()
+                    .enumerate()
+                    .map(|(part, receipt)| {
+                        let name = self
+                            .target_name(idx, part)
+                            .unwrap_or_else(|| receipt.file_name().to_string());
+                        (PathBuf::from(&receipt.path), name)
+                    })
+                    .collect();
+
+                PackageRow {
+                    row: idx,
+                    transaction: self.transactions[idx].as_ref().ok().cloned(),
+                    hidden: meta.map(|m| m.hidden).unwrap_or_default(),
+                    category: meta.and_then(|m| m.category.clone()),
+                    no_receipt: meta.and_then(|m| m.no_receipt.clone()),
+                    receipts,
+                }
+            })
             .collect();
-        // tracing::info!(
-        //     "update hidden: {:?} ({:?})",
-        //     self.visible_rows,
-        //     self.visible_rows.len()
-        // );
+
+        let extension = format.extension();
+        let file_name = self
+            .input_file
+            .file_stem()
+            .map(|f| format!("{}.{extension}", f.to_string_lossy()))
+            .unwrap_or_else(|| format!("receipts.{extension}"));
+        let title = self
+            .input_file
+            .file_name()
+            .map(|f| f.to_string_lossy().to_string())
+            .unwrap_or_default();
+        self.package_export.start(rows, format, title, file_name);
     }
 
-    pub fn reread_pdfs(&mut self) {
-        self.pdfs = find_pdfs(self.input_file.parent().unwrap());
+    fn update_summary(&mut self) {
+        self.summary = Summary::new(
+            self.transactions
+                .iter()
+                .enumerate()
+                .map(|(idx, t)| (t.as_ref().ok(), self.meta(idx))),
+            self.columns.charges_positive,
+        );
+        self.reconciliation = Reconciliation::new(self.state.balances, &self.transactions);
+    }
 
-        // tracing::info!("found pdfs: {}", self.pdfs.len());
+    /// applies the filter bar and the sorting
+    fn update_visible_rows(&mut self) {
+        let filter = self.table_filter.active();
+        let mut visible: Vec<usize> = (0..self.rows.len())
+            .filter(|idx| {
+                let meta = self.meta(*idx);
+                let status = RowStatus {
+                    hidden: meta.map(|m| m.hidden).unwrap_or_default(),
+                    assigned: meta
+                        .map(|m| m.has_receipts() || m.no_receipt.is_some())
+                        .unwrap_or_default(),
+                    wrongly_named: self.has_wrong_names(*idx),
+                };
+                filter.matches(
+                    &self.rows[*idx].cells,
+                    self.transactions[*idx].as_ref().ok(),
+                    &status,
+                    self.state.show_hidden,
+                )
+            })
+            .collect();
+
+        if let Some(sort) = self.sort {
+            let cell_order = match sort.column {
+                SortColumn::Cell(column) => {
+                    CellOrder::for_column(self.rows.iter().map(|row| row.cell(Some(column))))
+                }
+                _ => CellOrder::Text,
+            };
+            visible.sort_by(|a, b| {
+                let ordering = match sort.column {
+                    SortColumn::Index => a.cmp(b),
+                    SortColumn::Cell(column) => cell_order.compare(
+                        self.rows[*a].cell(Some(column)),
+                        self.rows[*b].cell(Some(column)),
+                    ),
+                    SortColumn::Receipt => {
+                        let name = |idx: usize| {
+                            self.meta(idx)
+                                .map(|m| m.receipt_file_names().to_lowercase())
+                                .unwrap_or_default()
+                        };
+                        name(*a).cmp(&name(*b))
+                    }
+                };
+                if sort.ascending {
+                    ordering
+                } else {
+                    ordering.reverse()
+                }
+            });
+        }
+
+        self.visible_rows = visible;
+    }
+
+    /// shared receipts are only checked for the first row they are assigned to
+    fn has_wrong_names(&self, idx: usize) -> bool {
+        let (Some(meta), Ok(transaction)) = (self.meta(idx), self.transactions[idx].as_ref())
+        else {
+            return false;
+        };
+
+        meta.receipts.iter().enumerate().any(|(part, receipt)| {
+            let is_owner = self
+                .shared_receipts
+                .get(&receipt.path)
+                .and_then(|rows| rows.first())
+                .map_or(true, |owner| *owner == idx);
+            is_owner
+                && !meta.is_name_correct(
+                    part,
+                    idx,
+                    transaction,
+                    self.state.name_template(),
+                    self.state.convert_images,
+                )
+        })
+    }
+
+    fn update_sources(&mut self) {
+        let base = self.input_file.parent().unwrap_or(Path::new("."));
+        let folders = self.state.receipt_folders();
+        let kinds = self.state.receipt_kinds();
+
+        self.sources =
+            ReceiptSources::new(base, &folders, kinds.clone(), &self.state.ignore_patterns)
+                .unwrap_or_else(|e| {
+                    self.error = Some(format!("invalid ignore pattern: {e:#}"));
+                    ReceiptSources::new(base, &folders, kinds, &[]).unwrap_or_default()
+                });
+        self.watch_pending = true;
+    }
+
+    /// walks the receipt folders, only needed when they changed or on refresh
+    pub fn reread_files(&mut self) {
+        let found = self.sources.find();
+        self.found_receipts = found.receipts;
+        self.unreadable = found.errors;
+
+        self.update_receipts();
+    }
+
+    /// splits the receipts found into assigned and unassigned ones
+    fn update_receipts(&mut self) {
+        let assigned: HashSet<String> = self
+            .assigned_rows()
+            .flat_map(RowMetaData::receipt_paths)
+            .map(str::to_string)
+            .collect();
+        let is_assigned = |p: &Path| p.to_str().is_some_and(|p| assigned.contains(p));
 
-        self.pdfs = self
-            .pdfs
+        // receipts that were assigned until now may have just been renamed
+        let unassigned_before: HashSet<PathBuf> = self.receipts.drain(..).collect();
+        self.found_receipts
+            .retain(|p| is_assigned(p) || unassigned_before.contains(p) || p.exists());
+
+        self.receipts = self
+            .found_receipts
             .iter()
-            .filter(|p| {
-                !self.state.row_meta_data.iter().any(|e| match &e.receipt {
-                    None => false,
-                    Some(e) => {
-                        let match_found = p.to_str().map_or(false, |p| p == e);
-                        // info!("found match: {:?} / '{}'", p, e);
-                        match_found
+            .filter(|p| !is_assigned(p))
+            .cloned()
+            .collect();
+
+        self.matcher.scan(&self.receipts);
+        self.thumbnails.load(&self.receipts);
+        self.hash_receipts();
+        self.update_suggestions();
+        self.update_missing_receipts();
+        self.update_shared_receipts();
+    }
+
+    /// hashes unassigned and assigned receipts to find identical files
+    fn hash_receipts(&mut self) {
+        let assigned: Vec<PathBuf> = self
+            .assigned_rows()
+            .flat_map(RowMetaData::receipt_paths)
+            .map(PathBuf::from)
+            .filter(|p| p.exists())
+            .collect();
+
+        let mut paths = self.receipts.clone();
+        paths.extend(assigned);
+        self.hashes.scan(&paths);
+        self.update_duplicates();
+    }
+
+    fn update_duplicates(&mut self) {
+        self.duplicates.clear();
+        for group in self.hashes.groups(&self.receipts) {
+            for idx in &group {
+                self.duplicates
+                    .insert(self.receipts[*idx].clone(), group.clone());
+            }
+        }
+    }
+
+    fn update_missing_receipts(&mut self) {
+        self.missing_receipts = self
+            .assigned_rows()
+            .flat_map(RowMetaData::receipt_paths)
+            .filter(|receipt| !Path::new(receipt).exists())
+            .map(str::to_string)
+            .collect();
+    }
+
+    fn update_shared_receipts(&mut self) {
+        let mut rows = HashMap::<String, Vec<usize>>::new();
+        for (idx, id) in self.row_ids.iter().enumerate() {
+            let paths = self
+                .state
+                .rows
+                .get(id)
+                .into_iter()
+                .flat_map(RowMetaData::receipt_paths);
+            for path in paths {
+                rows.entry(path.to_string()).or_default().push(idx);
+            }
+        }
+
+        rows.retain(|_, rows| rows.len() > 1);
+        self.shared_receipts = rows;
+    }
+
+    fn watch_files(&mut self, ctx: &egui::Context) {
+        if self.watch_pending {
+            self.watch_pending = false;
+            self.watcher = match ReceiptWatcher::new(self.sources.folders(), ctx.clone()) {
+                Ok((watcher, errors)) => {
+                    if !errors.is_empty() {
+                        self.error = Some(errors.join("\n"));
                     }
-                })
+                    Some(watcher)
+                }
+                Err(e) => {
+                    tracing::error!("file watcher error: {}", e);
+                    self.error = Some(format!("watching files failed: {e}"));
+                    None
+                }
+            };
+        }
+
+        let changed = match self.watcher.as_ref() {
+            Some(watcher) => watcher.poll(),
+            None => return,
+        };
+        if !changed.is_empty() {
+            self.apply_file_changes(changed);
+        }
+    }
+
+    /// updates the file list with the paths the watcher reported
+    fn apply_file_changes(&mut self, changed: BTreeSet<PathBuf>) {
+        // a folder moved in may contain any number of receipts
+        if changed.iter().any(|p| self.sources.accepts_folder(p)) {
+            self.reread_files();
+            return;
+        }
+
+        self.found_receipts.retain(|p| p.exists());
+        for path in changed {
+            self.preview.invalidate(&path);
+            if path.exists() && !self.found_receipts.contains(&path) && self.sources.accepts(&path)
+            {
+                tracing::debug!("new receipt: {:?}", path);
+                self.found_receipts.push(path);
+            }
+        }
+
+        self.update_receipts();
+    }
+
+    fn update_suggestions(&mut self) {
+        let unassigned: Vec<_> = self
+            .transactions
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, t)| Some((idx, t.as_ref().ok()?)))
+            .filter(|(idx, _)| {
+                self.meta(*idx)
+                    .map(|m| !m.has_receipts() && !m.hidden && m.no_receipt.is_none())
+                    .unwrap_or_default()
             })
-            .cloned()
-            .collect::<Vec<_>>();
+            .collect();
 
-        // info!("pdfs after filter: {}", self.pdfs.len());
+        self.matcher.update(&self.receipts, &unassigned);
+    }
+
+    fn accept_suggestions(&self, suggestions: &[Suggestion]) -> Option<Command> {
+        let commands: Vec<_> = suggestions
+            .iter()
+            .filter_map(|s| {
+                let id = self.row_ids.get(s.row)?;
+                let meta = self.state.rows.get(id)?;
+                let receipt = s.receipt.to_string_lossy().to_string();
+                Some(Command::edit(id, meta, |m| m.assign(receipt)))
+            })
+            .collect();
+
+        (!commands.is_empty()).then_some(Command::Batch(commands))
     }
 
     pub fn populate_menu(&mut self, ui: &mut Ui) {
+        ui.label(format!("Format: {}", self.format.name()));
+        if self.save_blocked {
+            ui.colored_label(Color32::RED, "Saving disabled");
+        }
+        if ui.button("Column Mapping…").clicked() {
+            self.mapping_wizard.open(self.columns);
+            ui.close_menu();
+        }
+        if ui.button("Settings…").clicked() {
+            self.settings.open(ProjectSettings {
+                template: self.state.name_template().to_string(),
+                receipt_kinds: self.state.receipt_kinds(),
+                convert_images: self.state.convert_images,
+                receipt_folders: self.state.receipt_folders.clone(),
+                ignore_patterns: self.state.ignore_patterns.clone(),
+            });
+            ui.close_menu();
+        }
+        if ui.button("Reconcile…").clicked() {
+            self.reconcile_window
+                .open(self.state.balances, self.statement_balances);
+            ui.close_menu();
+        }
+        ui.separator();
+
+        if ui
+            .add_enabled(
+                self.history.can_undo(),
+                egui::Button::new("Undo")
+                    .shortcut_text(ui.ctx().format_shortcut(&self.shortcut_undo)),
+            )
+            .clicked()
+        {
+            self.undo();
+            ui.close_menu();
+        }
+        if ui
+            .add_enabled(
+                self.history.can_redo(),
+                egui::Button::new("Redo")
+                    .shortcut_text(ui.ctx().format_shortcut(&self.shortcut_redo)),
+            )
+            .clicked()
+        {
+            self.redo();
+            ui.close_menu();
+        }
+        ui.separator();
+
+        let suggestions = self.matcher.suggestions().len();
+        if ui
+            .add_enabled(
+                suggestions > 0,
+                egui::Button::new(format!("Accept All Suggestions ({suggestions})")),
+            )
+            .clicked()
+        {
+            if let Some(command) = self.accept_suggestions(self.matcher.suggestions()) {
+                self.execute(command);
+            }
+            ui.close_menu();
+        }
+
+        if ui.button("Rename All Assigned…").clicked() {
+            self.open_rename_all();
+            ui.close_menu();
+        }
+
+        if ui
+            .add_enabled(
+                !self.package_export.is_busy(),
+                egui::Button::new("Export Package…"),
+            )
+            .on_hover_text(
+                "zip of the receipts under their new names, the originals stay as they are",
+            )
+            .clicked()
+        {
+            self.start_export(ExportFormat::Package);
+            ui.close_menu();
+        }
+
+        if ui
+            .add_enabled(
+                !self.package_export.is_busy(),
+                egui::Button::new("Export PDF Bundle…"),
+            )
+            .on_hover_text("one pdf with a cover sheet listing all rows, followed by the receipts")
+            .clicked()
+        {
+            self.start_export(ExportFormat::Bundle);
+            ui.close_menu();
+        }
+
         if ui.button("Clear All").clicked() {
-            self.state
-                .row_meta_data
-                .iter_mut()
-                .for_each(|e| e.receipt = None);
-            self.reread_pdfs();
+            let commands: Vec<_> = self
+                .row_ids
+                .iter()
+                .filter_map(|id| self.state.rows.get(id).map(|meta| (id, meta)))
+                .filter(|(_, meta)| meta.has_receipts())
+                .map(|(id, meta)| Command::edit(id, meta, RowMetaData::clear))
+                .collect();
+            if !commands.is_empty() {
+                self.execute(Command::Batch(commands));
+            }
             ui.close_menu();
         }
         if ui
@@ -152,13 +911,29 @@ impl Project {
             )
             .clicked()
         {
-            self.reread_pdfs();
+            self.reread_files();
+            ui.close_menu();
+        }
+
+        if ui.button("Restore Backup…").clicked() {
+            match self.backup_dir() {
+                Ok(dir) => self.backups = Some(backup::list(&dir)),
+                Err(e) => tracing::error!("backup dir error: {}", e),
+            }
             ui.close_menu();
         }
 
         if ui
             .checkbox(&mut self.state.show_hidden, "Show Hidden")
             .clicked()
+        {
+            self.update_visible_rows();
+            ui.close_menu();
+        }
+
+        if ui
+            .checkbox(&mut self.preview.open, "Show Preview")
+            .clicked()
         {
             ui.close_menu();
         }
@@ -168,213 +943,1150 @@ impl Project {
         ui.vertical_centered(|ui| {
             ui.heading("Files");
         });
+        ui.add(egui::TextEdit::singleline(&mut self.file_filter).hint_text("search"));
+        let filter = self.file_filter.trim().to_lowercase();
+
         egui::ScrollArea::vertical().show(ui, |ui| {
-            let id_source = "my_drag_and_drop_demo";
-            for (idx, pdf) in self.pdfs.iter().enumerate() {
-                let item_id = Id::new(id_source).with(idx);
+            let receipts = self.receipts.clone();
+            let duplicates = self.duplicates.clone();
+
+            for (idx, receipt) in receipts.iter().enumerate() {
+                match duplicates.get(receipt) {
+                    Some(group) if group[0] == idx => {
+                        if !group
+                            .iter()
+                            .any(|idx| self.file_matches(&receipts[*idx], &filter))
+                        {
+                            continue;
+                        }
+                        ui.group(|ui| {
+                            ui.colored_label(
+                                Color32::YELLOW,
+                                format!("{} identical files", group.len()),
+                            );
+                            for idx in group {
+                                self.draw_file(ui, *idx, &receipts[*idx], &filter);
+                            }
+                        });
+                    }
+                    // drawn with the first file of its group
+                    Some(_) => {}
+                    None => self.draw_file(ui, idx, receipt, &filter),
+                }
+            }
+
+            self.draw_assigned(ui);
+
+            if !self.orphans.is_empty() {
+                self.draw_orphans(ui);
+            }
+
+            if !self.unreadable.is_empty() {
+                ui.separator();
+                ui.collapsing(
+                    RichText::new(format!("{} unreadable entries", self.unreadable.len()))
+                        .color(Color32::RED),
+                    |ui| {
+                        for error in &self.unreadable {
+                            ui.label(error);
+                        }
+                    },
+                );
+            }
+        });
+    }
+
+    fn file_matches(&self, receipt: &Path, filter: &str) -> bool {
+        filter.is_empty()
+            || self
+                .matcher
+                .text(receipt)
+                .map(|text| text.contains(filter))
+                .unwrap_or_else(|| receipt.to_string_lossy().to_lowercase().contains(filter))
+    }
+
+    fn draw_file(&mut self, ui: &mut Ui, idx: usize, receipt: &Path, filter: &str) {
+        if !self.file_matches(receipt, filter) {
+            return;
+        }
+
+        let kind = ReceiptKind::from_path(receipt).unwrap_or(ReceiptKind::Pdf);
+        let thumbnail = self.thumbnails.get(receipt);
+
+        let item_id = Id::new("my_drag_and_drop_demo").with(idx);
+        Self::drag_source(ui, item_id, |ui| {
+            let filename = receipt
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string())
+                .unwrap_or_default();
+            ui.horizontal(|ui| {
+                match thumbnail {
+                    Some(texture) => {
+                        let size = texture.size_vec2();
+                        ui.image(texture, size * (THUMBNAIL_HEIGHT / size.y))
+                    }
+                    None => ui.label(kind.icon()),
+                };
+                ui.label(filename);
+            });
+        })
+        .map(|r| {
+            if r.hovered() {
+                self.preview.hover(receipt);
+            }
+            if r.clicked() {
+                self.preview.select(receipt);
+            }
+            r.context_menu(|ui| {
+                if ui.button("open").clicked() {
+                    ui.close_menu();
+                    open_receipt(receipt);
+                }
+            })
+        });
+
+        if ui.memory_mut(|mem| mem.is_being_dragged(item_id)) {
+            self.drag_receipt = Some(receipt.to_path_buf());
+        }
+    }
+
+    /// assigned receipts can be dragged onto further rows to share them
+    fn draw_assigned(&mut self, ui: &mut Ui) {
+        let mut assigned: Vec<(&str, Vec<usize>)> = Vec::new();
+        for (idx, meta) in self
+            .row_ids
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, id)| self.state.rows.get(id).map(|meta| (idx, meta)))
+        {
+            for path in meta.receipt_paths() {
+                match assigned.iter_mut().find(|(p, _)| *p == path) {
+                    Some((_, rows)) => rows.push(idx),
+                    None => assigned.push((path, vec![idx])),
+                }
+            }
+        }
+
+        if assigned.is_empty() {
+            return;
+        }
+
+        ui.separator();
+        let mut dragged = None;
+        let mut hovered = None;
+        let mut selected = None;
+        ui.collapsing(format!("Assigned ({})", assigned.len()), |ui| {
+            for (path, rows) in &assigned {
+                let item_id = Id::new("assigned_receipt").with(path);
+                let file_name = Path::new(path)
+                    .file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                let label = if rows.len() > 1 {
+                    format!("⇄ {file_name}")
+                } else {
+                    file_name
+                };
+                let rows = rows
+                    .iter()
+                    .map(|idx| format!("{idx:0>3}"))
+                    .collect::<Vec<_>>()
+                    .join(", ");
+
                 Self::drag_source(ui, item_id, |ui| {
-                    let filename = pdf
-                        .file_name()
-                        .map(|f| f.to_string_lossy().to_string())
-                        .unwrap_or_default();
-                    ui.label(filename);
+                    ui.label(label);
                 })
                 .map(|r| {
-                    r.context_menu(|ui| {
-                        if ui.button("open").clicked() {
-                            ui.close_menu();
-                            opener::open(pdf).unwrap_or_default();
-                        }
-                    })
+                    if r.hovered() {
+                        hovered = Some(PathBuf::from(path));
+                    }
+                    if r.clicked() {
+                        selected = Some(PathBuf::from(path));
+                    }
+                    r.on_hover_text(format!("rows {rows}, drop on another row to share"))
+                        .context_menu(|ui| {
+                            if ui.button("open").clicked() {
+                                ui.close_menu();
+                                open_receipt(Path::new(path));
+                            }
+                        })
                 });
 
                 if ui.memory_mut(|mem| mem.is_being_dragged(item_id)) {
-                    self.drag_row = Some(idx);
+                    dragged = Some(PathBuf::from(path));
                 }
             }
         });
+
+        if let Some(path) = hovered {
+            self.preview.hover(&path);
+        }
+        if let Some(path) = selected {
+            self.preview.select(&path);
+        }
+        if dragged.is_some() {
+            self.drag_receipt = dragged;
+        }
+    }
+
+    /// assignments whose transaction is not part of the statement (anymore)
+    fn draw_orphans(&mut self, ui: &mut Ui) {
+        ui.separator();
+        ui.vertical_centered(|ui| {
+            ui.heading("Orphaned");
+        });
+
+        let mut discard = None;
+        for id in &self.orphans {
+            let Some(meta) = self.state.rows.get(id) else {
+                continue;
+            };
+
+            ui.colored_label(Color32::YELLOW, meta.receipt_file_names())
+                .on_hover_text(id)
+                .context_menu(|ui| {
+                    if ui.button("discard").clicked() {
+                        discard = Some(id.clone());
+                        ui.close_menu();
+                    }
+                });
+        }
+
+        if let Some(id) = discard {
+            self.state.rows.remove(&id);
+            self.update_orphans();
+        }
     }
 
     pub fn draw(&mut self, ctx: &egui::Context, ui: &mut Ui) {
+        self.watch_files(ctx);
+
+        if self.matcher.poll() {
+            self.update_suggestions();
+            if !self.matcher.is_busy() {
+                // receipts found while scanning are picked up now
+                self.matcher.scan(&self.receipts);
+            }
+        }
+        if self.thumbnails.poll(ctx) {
+            // images found while loading are picked up now
+            self.thumbnails.load(&self.receipts);
+        }
+        if self.hashes.poll() {
+            self.update_duplicates();
+            if !self.hashes.is_busy() {
+                // files that changed while hashing are picked up now
+                self.hash_receipts();
+            }
+        }
+        match self.package_export.poll() {
+            Some(Ok(exported)) if exported.skipped.is_empty() => {
+                self.notice = Some(format!("exported {}", exported.path.display()));
+            }
+            Some(Ok(exported)) => {
+                let names = exported
+                    .skipped
+                    .iter()
+                    .filter_map(|p| p.file_name())
+                    .map(|f| f.to_string_lossy())
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                self.notice = Some(format!(
+                    "exported {}, {} receipts were left out: {}",
+                    exported.path.display(),
+                    exported.skipped.len(),
+                    names
+                ));
+            }
+            Some(Err(e)) => {
+                tracing::error!("export error: {:#}", e);
+                self.error = Some(format!("export failed: {e:#}"));
+            }
+            None => {}
+        }
+        if self.matcher.is_busy()
+            || self.thumbnails.is_busy()
+            || self.hashes.is_busy()
+            || self.package_export.is_busy()
+        {
+            ctx.request_repaint_after(std::time::Duration::from_millis(250));
+        }
+
         egui::SidePanel::left("right_panel")
             .default_width(150.0)
             .show_inside(ui, |ui| {
                 self.draw_files(ui);
             });
 
+        self.preview.show(ui);
+
+        egui::TopBottomPanel::bottom("summary_panel").show_inside(ui, |ui| {
+            self.draw_summary(ui);
+        });
+
         egui::CentralPanel::default().show_inside(ui, |ui| {
             ui.vertical_centered(|ui| {
                 ui.heading("Table");
+
+                let mut dismiss = false;
+                if let Some(error) = &self.error {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(Color32::RED, error);
+                        dismiss = ui.small_button("dismiss").clicked();
+                    });
+                }
+                if dismiss {
+                    self.error = None;
+                }
+
+                let mut dismiss = false;
+                if let Some(notice) = &self.notice {
+                    ui.horizontal(|ui| {
+                        ui.colored_label(Color32::DARK_GREEN, notice);
+                        dismiss = ui.small_button("dismiss").clicked();
+                    });
+                }
+                if dismiss {
+                    self.notice = None;
+                }
+
+                if self.package_export.is_busy() {
+                    ui.label("exporting…");
+                }
+
+                if self.matcher.is_busy() {
+                    ui.label("extracting receipt texts…");
+                }
+
+                let errors = self.transactions.iter().filter(|t| t.is_err()).count();
+                if errors > 0 {
+                    ui.colored_label(
+                        Color32::RED,
+                        format!("{errors} rows could not be parsed (hover ⚠ for details)"),
+                    );
+                }
             });
+            self.draw_filter_bar(ui);
             self.draw_table(ui, ctx);
         });
+
+        self.draw_load_error(ctx);
+
+        if let Some(command) = self.rename_dialog.show(ctx) {
+            self.execute(command);
+        }
+        self.draw_duplicate_warning(ctx);
+
+        if self.settings.is_open() {
+            let preview = self.template_preview(self.settings.template());
+            if let Some(settings) = self.settings.show(ctx, &preview) {
+                let sources_changed = self.state.receipt_kinds() != settings.receipt_kinds
+                    || self.state.receipt_folders != settings.receipt_folders
+                    || self.state.ignore_patterns != settings.ignore_patterns;
+
+                self.state.name_template = Some(settings.template);
+                self.state.receipt_kinds = Some(settings.receipt_kinds);
+                self.state.convert_images = settings.convert_images;
+                self.state.receipt_folders = settings.receipt_folders;
+                self.state.ignore_patterns = settings.ignore_patterns;
+                if sources_changed {
+                    self.update_sources();
+                    self.reread_files();
+                } else {
+                    self.update_receipts();
+                }
+            }
+        }
+        self.draw_backups(ctx);
+
+        if let Some(balances) = self.reconcile_window.show(ctx, &self.reconciliation) {
+            self.state.balances = balances;
+            self.update_summary();
+        }
+
+        if let Some(mapping) = self.mapping_wizard.show(ctx, &self.rows, self.max_cells) {
+            self.columns = mapping;
+            self.update_transactions();
+            self.update_visible_rows();
+            self.update_summary();
+            if let Err(e) = self.save_mapping() {
+                tracing::error!("saving mapping error: {}", e);
+            }
+        }
+    }
+
+    fn draw_summary(&self, ui: &mut Ui) {
+        let summary = &self.summary;
+        let totals =
+            |name: &str, totals: &Totals| format!("{name}: {} ({})", totals.count, totals.amount);
+
+        ui.horizontal(|ui| {
+            ui.add(
+                egui::ProgressBar::new(summary.progress())
+                    .desired_width(150.0)
+                    .show_percentage(),
+            );
+            ui.label(totals("total", &summary.total));
+            ui.separator();
+            ui.colored_label(Color32::DARK_GREEN, totals("assigned", &summary.assigned));
+            ui.separator();
+            ui.label(totals("unassigned", &summary.unassigned));
+            ui.separator();
+            ui.colored_label(Color32::GRAY, totals("hidden", &summary.hidden));
+            ui.separator();
+            if summary.no_receipt.count > 0 {
+                ui.colored_label(
+                    Color32::GRAY,
+                    totals("no receipt needed", &summary.no_receipt),
+                );
+                ui.separator();
+            }
+            let missing = RichText::new(format!("missing receipts: {}", summary.missing_charges));
+            if summary.missing_charges == transaction::Amount::ZERO {
+                ui.label(missing);
+            } else {
+                ui.label(missing.color(Color32::YELLOW))
+                    .on_hover_text("charges of unassigned rows, refunds are left out");
+            }
+            match self.reconciliation.is_reconciled() {
+                Some(true) => {
+                    ui.separator();
+                    ui.colored_label(Color32::DARK_GREEN, "✔ reconciled");
+                }
+                Some(false) => {
+                    ui.separator();
+                    ui.colored_label(Color32::RED, "⚠ not reconciled")
+                        .on_hover_text("see Reconcile… for details");
+                }
+                None => {}
+            }
+            if summary.unparsed > 0 {
+                ui.separator();
+                ui.colored_label(
+                    Color32::RED,
+                    format!("{} rows without amount", summary.unparsed),
+                );
+            }
+        });
+    }
+
+    /// titles from the header row, the mapped role or the column number
+    fn column_names(&self) -> Vec<String> {
+        (0..self.max_cells)
+            .map(|column| {
+                let title = self
+                    .header
+                    .as_ref()
+                    .map(|header| header.cell(Some(column)).trim())
+                    .unwrap_or_default();
+                if !title.is_empty() {
+                    return title.to_string();
+                }
+                self.columns
+                    .role_of(column)
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("{}", column + 1))
+            })
+            .collect()
+    }
+
+    fn draw_filter_bar(&mut self, ui: &mut Ui) {
+        let mut changed = false;
+        let filter = &mut self.table_filter;
+        let invalid_amount = filter.min_amount().is_err() || filter.max_amount().is_err();
+        let invalid_date = filter.from().is_err() || filter.to().is_err();
+
+        ui.horizontal(|ui| {
+            changed |= ui
+                .add(
+                    egui::TextEdit::singleline(&mut filter.text)
+                        .hint_text("search")
+                        .desired_width(150.0),
+                )
+                .changed();
+
+            ui.label("amount");
+            changed |= filter_input(ui, &mut filter.min_amount, "min", invalid_amount);
+            changed |= filter_input(ui, &mut filter.max_amount, "max", invalid_amount);
+
+            ui.label("date");
+            changed |= filter_input(ui, &mut filter.from, "YYYY-MM-DD", invalid_date);
+            changed |= filter_input(ui, &mut filter.to, "YYYY-MM-DD", invalid_date);
+
+            egui::ComboBox::from_id_source("status_filter")
+                .selected_text(filter.status.name())
+                .show_ui(ui, |ui| {
+                    for status in StatusFilter::ALL {
+                        changed |= ui
+                            .selectable_value(&mut filter.status, status, status.name())
+                            .changed();
+                    }
+                });
+
+            if filter.is_active() && ui.small_button("clear").clicked() {
+                *filter = TableFilter::default();
+                changed = true;
+            }
+
+            ui.label(format!(
+                "{} of {} rows",
+                self.visible_rows.len(),
+                self.rows.len()
+            ));
+        });
+
+        if changed {
+            self.update_visible_rows();
+        }
     }
 
     fn draw_table(&mut self, ui: &mut egui::Ui, ctx: &egui::Context) {
-        use egui_extras::{Column, TableBuilder};
+        let template = self.state.name_template().to_string();
+        let convert_images = self.state.convert_images;
+        let mut categories: Vec<String> = self
+            .state
+            .rows
+            .values()
+            .filter_map(|m| m.category.clone())
+            .collect();
+        categories.sort();
+        categories.dedup();
+
+        // shared receipts are named after the first row they are assigned to
+        let shared_targets: HashMap<String, String> = self
+            .shared_receipts
+            .iter()
+            .filter_map(|(path, rows)| {
+                let owner = *rows.first()?;
+                let meta = self.meta(owner)?;
+                let transaction = self.transactions[owner].as_ref().ok()?;
+                let part = meta.receipts.iter().position(|r| r.path == *path)?;
+                let target =
+                    meta.target_file_name(part, owner, transaction, &template, convert_images)?;
+                Some((path.clone(), target))
+            })
+            .collect();
+
+        let layout = self.state.table_layout.clone();
+        let mut frame = TableFrame {
+            contains_pointer: ui.ui_contains_pointer(),
+            converted_dir: self.converted_dir().unwrap_or_default(),
+            template,
+            convert_images,
+            categories,
+            shared_targets,
+            sort: self.sort,
+            column_names: self.column_names(),
+            visible_columns: layout.visible(self.max_cells),
+            layout,
+            command: None,
+            rejected: None,
+            preview_hovered: None,
+            preview_selected: None,
+            sort_clicked: None,
+        };
+
+        // a new table id makes egui pick up the saved widths after the columns changed
+        let table_id = Id::new((&self.input_file, &frame.visible_columns));
+        ui.push_id(table_id, |ui| self.draw_table_rows(ui, ctx, &mut frame));
+
+        let TableFrame {
+            command,
+            rejected,
+            layout,
+            sort_clicked,
+            preview_hovered,
+            preview_selected,
+            ..
+        } = frame;
+
+        if let Some(command) = command {
+            self.execute(command);
+        }
+        if let Some(rejected) = rejected {
+            self.matcher.reject(&rejected);
+        }
+        if layout != self.state.table_layout {
+            self.state.table_layout = layout;
+        }
+        if let Some(column) = sort_clicked {
+            self.sort = Sort::toggle(self.sort, column);
+            self.update_visible_rows();
+        }
+        if let Some(path) = preview_hovered {
+            self.preview.hover(&path);
+        }
+        if let Some(path) = preview_selected {
+            self.preview.select(&path);
+        }
+
+        if ui.input(|input| input.pointer.any_released()) {
+            self.check_drop();
+        }
+    }
 
-        let contains_pointer = ui.ui_contains_pointer();
+    fn draw_table_rows(&mut self, ui: &mut Ui, ctx: &egui::Context, frame: &mut TableFrame) {
+        use egui_extras::{Column, TableBuilder};
 
-        TableBuilder::new(ui)
+        let mut builder = TableBuilder::new(ui)
             .striped(true)
             .auto_shrink([false; 2])
-            .columns(
-                Column::initial(30.0).at_least(10.0).clip(true),
-                self.max_cells + 2,
-            )
+            .columns(Column::initial(30.0).at_least(10.0).clip(true), 2);
+        let widths = frame
+            .visible_columns
+            .iter()
+            .map(|column| frame.layout.width(*column).unwrap_or(DEFAULT_COLUMN_WIDTH));
+        for width in widths {
+            builder = builder.column(Column::initial(width).at_least(10.0).clip(true));
+        }
+
+        builder
             .column(Column::remainder())
             .cell_layout(
                 egui::Layout::left_to_right(egui::Align::Center)
                     .with_cross_align(egui::Align::Center),
             )
             .resizable(true)
+            .header(20.0, |header| self.draw_table_header(header, frame))
             .body(|body| {
-                let rows = if self.state.show_hidden {
-                    self.rows.len()
-                } else {
-                    self.visible_rows.len()
-                };
+                body.rows(ROW_HEIGHT, self.visible_rows.len(), |row_index, row| {
+                    self.draw_table_row(row_index, row, ctx, frame)
+                });
+            });
+    }
 
-                let row_height = 18.0;
-                let mut update_hidden = false;
+    fn draw_table_header(&self, mut header: TableRow<'_, '_>, frame: &mut TableFrame) {
+        let mut sort_button = |ui: &mut Ui, name: &str, column: SortColumn| {
+            let text = format!("{}{}", name, Sort::arrow(frame.sort, column));
+            let response = ui.button(RichText::new(text).strong());
+            if response.clicked() {
+                frame.sort_clicked = Some(column);
+            }
+            response
+        };
 
-                body.rows(row_height, rows, |row_index, mut row| {
-                    let row_index = if self.state.show_hidden {
-                        row_index
-                    } else {
-                        self.visible_rows[row_index]
-                    };
+        header.col(|ui| {
+            ui.menu_button("☰", |ui| {
+                columns_menu(ui, &mut frame.layout, &frame.column_names)
+            })
+            .response
+            .on_hover_text("columns");
+        });
+        header.col(|ui| {
+            sort_button(ui, "#", SortColumn::Index)
+                .context_menu(|ui| columns_menu(ui, &mut frame.layout, &frame.column_names));
+        });
+        for (pos, column) in frame.visible_columns.iter().enumerate() {
+            let column = *column;
+            header.col(|ui| {
+                frame.layout.set_width(column, ui.max_rect().width());
+
+                let name = &frame.column_names[column];
+                let mut response = sort_button(ui, name, SortColumn::Cell(column));
+                if let Some(role) = self.columns.role_of(column) {
+                    if role != name {
+                        response = response.on_hover_text(role);
+                    }
+                }
+                response.context_menu(|ui| {
+                    if ui.button("hide column").clicked() {
+                        frame.layout.set_hidden(column, true);
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(pos > 0, egui::Button::new("move left"))
+                        .clicked()
+                    {
+                        frame
+                            .layout
+                            .move_column(column, -1, frame.column_names.len());
+                        ui.close_menu();
+                    }
+                    if ui
+                        .add_enabled(
+                            pos + 1 < frame.visible_columns.len(),
+                            egui::Button::new("move right"),
+                        )
+                        .clicked()
+                    {
+                        frame
+                            .layout
+                            .move_column(column, 1, frame.column_names.len());
+                        ui.close_menu();
+                    }
+                    ui.separator();
+                    columns_menu(ui, &mut frame.layout, &frame.column_names);
+                });
+            });
+        }
+        header.col(|ui| {
+            sort_button(ui, "Receipt", SortColumn::Receipt)
+                .context_menu(|ui| columns_menu(ui, &mut frame.layout, &frame.column_names));
+        });
+    }
+
+    fn draw_table_row(
+        &mut self,
+        row_index: usize,
+        mut row: TableRow<'_, '_>,
+        ctx: &egui::Context,
+        frame: &mut TableFrame,
+    ) {
+        let row_index = self.visible_rows[row_index];
+
+        let row_id = &self.row_ids[row_index];
+        let meta = self.state.rows.entry(row_id.clone()).or_default();
+
+        row.col(|ui| {
+            let mut hidden = meta.hidden;
+            if self.state.show_hidden || hidden {
+                if ui.checkbox(&mut hidden, "hide").changed() {
+                    frame.command = Some(Command::edit(row_id, meta, |m| m.hidden = hidden));
+                }
+            } else if ui.small_button("hide").clicked() {
+                frame.command = Some(Command::edit(row_id, meta, |m| m.hidden = true));
+            }
+        });
+
+        let is_hidden = meta.hidden;
+        let is_assigned = meta.has_receipts();
+
+        let transaction = self.transactions[row_index].as_ref();
+
+        row.col(|ui| {
+            ui.label(format!("{row_index:0>3}"));
+            if let Err(e) = transaction {
+                ui.colored_label(Color32::RED, "⚠")
+                    .on_hover_text(format!("{e:#}"));
+            }
+        });
+
+        for column in &frame.visible_columns {
+            let cell = self.rows[row_index].cell(Some(*column));
+            row.col(|ui| {
+                let row_hovered = frame.contains_pointer
+                    && ctx
+                        .pointer_hover_pos()
+                        .map(|pos| {
+                            let widget_pos = ui.next_widget_position().y;
+
+                            let cursor_height_div_2 = ROW_HEIGHT / 2.0;
+                            pos.y > widget_pos - cursor_height_div_2
+                                && pos.y < widget_pos + cursor_height_div_2
+                        })
+                        .unwrap_or_default();
+
+                if is_hidden {
+                    ui.style_mut().visuals.override_text_color = Some(Color32::GRAY);
+                } else if is_assigned {
+                    ui.style_mut().visuals.override_text_color = Some(Color32::DARK_GREEN);
+                }
+
+                let mut w = WidgetText::from(cell);
+                if row_hovered {
+                    w = w.background_color(Color32::from_gray(50));
+                }
+                ui.label(w);
+                if is_hidden {
+                    ui.reset_style();
+                }
+            });
+        }
+
+        let meta = &*self.state.rows.entry(row_id.clone()).or_default();
+
+        let transaction = transaction.ok();
+        let split_status = transaction.and_then(|t| meta.split_status(t));
+
+        row.col(|ui| {
+            // context menu responses, with the receipt part they belong to
+            let mut responses = Vec::new();
+
+            for (part, receipt) in meta.receipts.iter().enumerate() {
+                let is_name_correct = match frame.shared_targets.get(receipt.path.as_str()) {
+                    Some(target) => *target == receipt.path,
+                    None => transaction
+                        .map(|t| {
+                            meta.is_name_correct(
+                                part,
+                                row_index,
+                                t,
+                                &frame.template,
+                                frame.convert_images,
+                            )
+                        })
+                        .unwrap_or(true),
+                };
 
-                    let meta = &mut self.state.row_meta_data[row_index];
+                let name = match receipt.amount {
+                    Some(amount) => format!("{} ({})", receipt.file_name(), amount),
+                    None => receipt.file_name().to_string(),
+                };
 
-                    row.col(|ui| {
-                        if self.state.show_hidden {
-                            update_hidden = ui.checkbox(&mut meta.hidden, "hide").changed();
-                        } else if ui.small_button("hide").clicked() {
-                            meta.hidden = true;
-                            update_hidden = true;
+                let response = if self.missing_receipts.contains(&receipt.path) {
+                    let txt = RichText::new(format!("🔗 {name}"))
+                        .strikethrough()
+                        .color(Color32::RED);
+                    ui.add(Label::new(txt).sense(Sense::click()))
+                        .on_hover_text("file not found, it was moved or deleted outside of the app")
+                } else {
+                    let mut txt = WidgetText::from(name);
+                    if !is_name_correct {
+                        txt = txt.color(Color32::RED);
+                    }
+                    let response = ui.add(Label::new(txt).sense(Sense::click()));
+                    match receipt.original_name.as_deref() {
+                        Some(original) if original != receipt.file_name() => {
+                            response.on_hover_text(format!("original: {original}"))
                         }
-                    });
+                        _ => response,
+                    }
+                };
+                responses.push((Some(part), response));
+
+                if let Some(rows) = self.shared_receipts.get(&receipt.path) {
+                    let others = rows
+                        .iter()
+                        .filter(|idx| **idx != row_index)
+                        .map(|idx| format!("{idx:0>3}"))
+                        .collect::<Vec<_>>()
+                        .join(", ");
+                    ui.label(RichText::new("⇄ shared").small().color(Color32::LIGHT_BLUE))
+                        .on_hover_text(format!("also assigned to rows {others}"));
+                }
+            }
 
-                    let is_hidden = meta.hidden;
-                    let is_assigned = meta.receipt.is_some();
+            let is_being_dragged = ui.memory_mut(|mem| mem.is_anything_being_dragged());
 
-                    row.col(|ui| {
-                        ui.label(format!("{row_index:0>3}"));
-                    });
+            if meta.has_receipts() {
+                match split_status {
+                    Some(SplitStatus::Complete) => {
+                        ui.colored_label(Color32::DARK_GREEN, "✔")
+                            .on_hover_text("receipt amounts add up");
+                    }
+                    Some(SplitStatus::Difference(difference)) => {
+                        ui.colored_label(Color32::RED, "⚠").on_hover_text(format!(
+                            "receipt amounts differ from the transaction by {difference}"
+                        ));
+                    }
+                    Some(SplitStatus::MissingAmounts(missing)) => {
+                        ui.colored_label(Color32::YELLOW, "⚠")
+                            .on_hover_text(format!("{missing} receipts without amount"));
+                    }
+                    None => {}
+                }
 
-                    for cell in &self.rows[row_index].cells {
-                        row.col(|ui| {
-                            let row_hovered = contains_pointer
-                                && ctx
-                                    .pointer_hover_pos()
-                                    .map(|pos| {
-                                        let widget_pos = ui.next_widget_position().y;
-
-                                        let cursor_height_div_2 = row_height / 2.0;
-                                        pos.y > widget_pos - cursor_height_div_2
-                                            && pos.y < widget_pos + cursor_height_div_2
-                                    })
-                                    .unwrap_or_default();
-
-                            if is_hidden {
-                                ui.style_mut().visuals.override_text_color = Some(Color32::GRAY);
-                            } else if is_assigned {
-                                ui.style_mut().visuals.override_text_color =
-                                    Some(Color32::DARK_GREEN);
-                            }
+                if is_being_dragged {
+                    let response = Self::drop_target(ui, true, |ui| ui.label("+"))
+                        .response
+                        .on_hover_text("add as another receipt");
+                    responses.push((None, response));
+                }
+            } else {
+                let suggestion = self
+                    .matcher
+                    .suggestion_for_row(row_index)
+                    .filter(|_| meta.no_receipt.is_none());
+                let response = Self::drop_target(ui, true, |ui| match suggestion {
+                    Some(s) => ui.label(
+                        RichText::new(format!(
+                            "{} ({:.0}%)",
+                            s.receipt.file_name().unwrap_or_default().to_string_lossy(),
+                            s.confidence * 100.0
+                        ))
+                        .italics()
+                        .weak(),
+                    ),
+                    None => match &meta.no_receipt {
+                        Some(reason) => {
+                            ui.label(RichText::new(format!("no receipt: {reason}")).weak())
+                        }
+                        None => ui.label("-"),
+                    },
+                })
+                .response;
 
-                            let mut w = WidgetText::from(cell);
-                            if row_hovered {
-                                w = w.background_color(Color32::from_gray(50));
-                            }
-                            ui.label(w);
-                            if is_hidden {
-                                ui.reset_style();
-                            }
-                        });
+                if let Some(s) = suggestion {
+                    if ui.small_button("✔").on_hover_text("accept").clicked() {
+                        let receipt = s.receipt.to_string_lossy().to_string();
+                        frame.command = Some(Command::edit(row_id, meta, |m| m.assign(receipt)));
                     }
+                    if ui.small_button("✖").on_hover_text("reject").clicked() {
+                        frame.rejected = Some(s.clone());
+                    }
+                }
+
+                responses.push((None, response));
+            }
 
-                    let meta = &mut self.state.row_meta_data[row_index];
-                    let csv_row = &self.rows[row_index];
+            let mut hovered_label = false;
+            for (part, response) in responses {
+                hovered_label |= response.hovered();
 
-                    let can_accept_what_is_being_dragged = meta.receipt.is_none();
+                if let Some(receipt) = part.and_then(|p| meta.receipts.get(p)) {
+                    if response.hovered() {
+                        frame.preview_hovered = Some(PathBuf::from(&receipt.path));
+                    }
+                    if response.clicked() {
+                        frame.preview_selected = Some(PathBuf::from(&receipt.path));
+                    }
+                }
 
-                    let mut reread = false;
-                    let is_receipt_name_correct = meta.is_name_correct(row_index, csv_row);
+                response.context_menu(|ui| {
+                    if let Some(receipt) = part.and_then(|p| meta.receipts.get(p)) {
+                        let path = receipt.path.clone();
 
-                    row.col(|ui| {
-                        let response = match meta.get_receipt_filename() {
-                            Some(receipt) => {
-                                let mut txt = WidgetText::from(receipt);
-                                if !is_receipt_name_correct {
-                                    txt = txt.color(Color32::RED);
-                                }
-                                ui.add(Label::new(txt).sense(Sense::click()))
+                        if ui.button("remove").clicked() {
+                            frame.command = Some(Command::edit(row_id, meta, |m| m.remove(&path)));
+                            ui.close_menu();
+                        }
+                        if let Some(to) = receipt.original_path() {
+                            if ui.button("remove and restore original name").clicked() {
+                                frame.command = Some(Command::Batch(vec![
+                                    Command::Rename {
+                                        row: row_id.clone(),
+                                        from: path.clone(),
+                                        to,
+                                    },
+                                    Command::edit(row_id, meta, |m| m.remove(&path)),
+                                ]));
+                                ui.close_menu();
                             }
-                            None => {
-                                Self::drop_target(ui, can_accept_what_is_being_dragged, |ui| {
-                                    ui.label("-")
-                                })
-                                .response
+                        }
+                        if ui
+                            .add_enabled(transaction.is_some(), egui::Button::new("rename"))
+                            .clicked()
+                        {
+                            let target =
+                                frame
+                                    .shared_targets
+                                    .get(path.as_str())
+                                    .cloned()
+                                    .or_else(|| {
+                                        meta.target_file_name(
+                                            part.unwrap_or_default(),
+                                            row_index,
+                                            transaction?,
+                                            &frame.template,
+                                            frame.convert_images,
+                                        )
+                                    });
+                            if let Some(to) = target {
+                                frame.command = Some(Command::rename(
+                                    row_id,
+                                    path.clone(),
+                                    to,
+                                    &frame.converted_dir,
+                                ));
                             }
-                        };
-
-                        let hovered_label = response.hovered();
+                            ui.close_menu();
+                        }
+                        if ui.button("open").clicked() {
+                            open_receipt(Path::new(&path));
+                            ui.close_menu();
+                        }
 
-                        response.context_menu(|ui| {
-                            if ui.button("clear").clicked() {
-                                meta.receipt = None;
-                                reread = true;
-                                ui.close_menu();
+                        ui.menu_button("amount", |ui| {
+                            let mut amount = None;
+                            if ui.button("none").clicked() {
+                                amount = Some(None);
                             }
-                            if ui
-                                .add_enabled(meta.receipt.is_some(), egui::Button::new("rename"))
-                                .clicked()
-                            {
-                                meta.rename_pdf(row_index, csv_row);
-                                reread = true;
+                            ui.horizontal(|ui| {
+                                ui.add(
+                                    egui::TextEdit::singleline(&mut self.amount_input)
+                                        .hint_text("12,34")
+                                        .desired_width(80.0),
+                                );
+                                if ui.button("set").clicked() {
+                                    match transaction::Amount::parse(&self.amount_input) {
+                                        Ok(parsed) => {
+                                            amount = Some(Some(parsed.abs()));
+                                            self.amount_input.clear();
+                                        }
+                                        Err(e) => {
+                                            self.error = Some(format!("{e:#}"));
+                                        }
+                                    }
+                                }
+                            });
+
+                            if let Some(amount) = amount {
+                                frame.command = Some(Command::edit(row_id, meta, |m| {
+                                    if let Some(r) = m.receipts.iter_mut().find(|r| r.path == path)
+                                    {
+                                        r.amount = amount;
+                                    }
+                                }));
                                 ui.close_menu();
                             }
+                        });
 
-                            if meta.receipt.is_some() && ui.button("open").clicked() {
-                                opener::open(meta.receipt.clone().unwrap_or_default())
-                                    .unwrap_or_default();
-                                ui.close_menu();
+                        ui.separator();
+                    }
+
+                    if ui
+                        .add_enabled(meta.has_receipts(), egui::Button::new("clear"))
+                        .clicked()
+                    {
+                        frame.command = Some(Command::edit(row_id, meta, RowMetaData::clear));
+                        ui.close_menu();
+                    }
+
+                    ui.menu_button("category", |ui| {
+                        let mut category = None;
+                        if ui.button("none").clicked() {
+                            category = Some(None);
+                        }
+                        for c in &frame.categories {
+                            if ui.button(c).clicked() {
+                                category = Some(Some(c.clone()));
+                            }
+                        }
+                        ui.horizontal(|ui| {
+                            ui.text_edit_singleline(&mut self.category_input);
+                            if ui.button("add").clicked() && !self.category_input.trim().is_empty()
+                            {
+                                category = Some(Some(self.category_input.trim().to_string()));
+                                self.category_input.clear();
                             }
                         });
 
-                        let is_being_dragged = ui.memory_mut(|mem| mem.is_anything_being_dragged());
-                        if is_being_dragged && can_accept_what_is_being_dragged && hovered_label {
-                            self.drop_row = Some(row_index);
+                        if let Some(category) = category {
+                            frame.command =
+                                Some(Command::edit(row_id, meta, |m| m.category = category));
+                            ui.close_menu();
                         }
                     });
 
-                    if reread {
-                        self.reread_pdfs();
-                    }
-                });
+                    ui.menu_button("no receipt needed", |ui| {
+                        let mut reason = None;
+                        if ui
+                            .add_enabled(meta.no_receipt.is_some(), egui::Button::new("none"))
+                            .clicked()
+                        {
+                            reason = Some(None);
+                        }
+                        ui.horizontal(|ui| {
+                            ui.add(
+                                egui::TextEdit::singleline(&mut self.reason_input)
+                                    .hint_text("bank fee"),
+                            );
+                            if ui.button("set").clicked() && !self.reason_input.trim().is_empty() {
+                                reason = Some(Some(self.reason_input.trim().to_string()));
+                                self.reason_input.clear();
+                            }
+                        });
 
-                if update_hidden {
-                    self.update_hidden();
-                }
-            });
+                        if let Some(reason) = reason {
+                            frame.command =
+                                Some(Command::edit(row_id, meta, |m| m.no_receipt = reason));
+                            ui.close_menu();
+                        }
+                    });
+                });
+            }
 
-        if ui.input(|input| input.pointer.any_released()) {
-            self.check_drop();
-        }
+            if is_being_dragged && hovered_label {
+                self.drop_row = Some(row_index);
+            }
+        });
     }
 
     fn check_drop(&mut self) {
-        if let Some(source_row) = self.drag_row {
+        if let Some(receipt) = self.drag_receipt.as_ref() {
             if let Some(drop_row) = self.drop_row {
-                if let Some(meta) = self.state.row_meta_data.get_mut(drop_row) {
-                    meta.receipt = Some(self.pdfs[source_row].to_string_lossy().to_string());
-                    self.drag_row = None;
+                let meta = self
+                    .row_ids
+                    .get(drop_row)
+                    .and_then(|id| self.state.rows.get(id));
+                if meta.is_some() {
+                    let receipt = receipt.to_string_lossy().to_string();
+                    let duplicates = self.identical_receipts(drop_row, &receipt);
+                    self.drag_receipt = None;
                     self.drop_row = None;
-                    self.reread_pdfs();
+                    if duplicates.is_empty() {
+                        self.assign(drop_row, receipt);
+                    } else {
+                        self.pending_drop = Some((drop_row, receipt, duplicates));
+                    }
+                }
+            }
+        }
+    }
+
+    fn assign(&mut self, row: usize, receipt: String) {
+        let meta = self.row_ids.get(row).and_then(|id| self.state.rows.get(id));
+        if let Some(meta) = meta {
+            let command = Command::edit(&self.row_ids[row], meta, |m| m.assign(receipt));
+            self.execute(command);
+        }
+    }
+
+    /// receipts of other rows with the same content as `receipt`, formatted for display
+    fn identical_receipts(&self, row: usize, receipt: &str) -> Vec<String> {
+        let Some(hash) = self.hashes.get(Path::new(receipt)) else {
+            return Vec::new();
+        };
+
+        let mut res = Vec::new();
+        for (idx, id) in self.row_ids.iter().enumerate() {
+            if idx == row {
+                continue;
+            }
+            let paths = self
+                .state
+                .rows
+                .get(id)
+                .into_iter()
+                .flat_map(RowMetaData::receipt_paths);
+            for path in paths {
+                if path != receipt && self.hashes.get(Path::new(path)) == Some(hash) {
+                    let name = Path::new(path)
+                        .file_name()
+                        .map(|f| f.to_string_lossy().to_string())
+                        .unwrap_or_default();
+                    res.push(format!("row {idx:0>3}: {name}"));
+                }
+            }
+        }
+        res
+    }
+
+    fn draw_duplicate_warning(&mut self, ctx: &egui::Context) {
+        let Some((row, receipt, duplicates)) = self.pending_drop.as_ref() else {
+            return;
+        };
+
+        let mut assign = false;
+        let mut cancel = false;
+        egui::Window::new("Duplicate Receipt")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                let name = Path::new(receipt)
+                    .file_name()
+                    .map(|f| f.to_string_lossy().to_string())
+                    .unwrap_or_default();
+                ui.label(format!(
+                    "'{name}' has the same content as a receipt already assigned to:"
+                ));
+                for duplicate in duplicates {
+                    ui.colored_label(Color32::YELLOW, duplicate);
                 }
+                ui.label(format!("assign it to row {row:0>3} anyway?"));
+
+                ui.horizontal(|ui| {
+                    assign = ui.button("Assign Anyway").clicked();
+                    cancel = ui.button("Cancel").clicked();
+                });
+            });
+
+        if assign {
+            if let Some((row, receipt, _)) = self.pending_drop.take() {
+                self.assign(row, receipt);
             }
+        } else if cancel {
+            self.pending_drop = None;
         }
     }
 
@@ -430,7 +2142,7 @@ impl Project {
             let response = ui.scope(body).response;
 
             // Check for drags:
-            let response = ui.interact(response.rect, id, Sense::drag());
+            let response = ui.interact(response.rect, id, Sense::click_and_drag());
             if response.dragged_by(PointerButton::Primary) {
                 ui.memory_mut(|mem| mem.set_dragged_id(id));
                 ui.output_mut(|out| out.cursor_icon = CursorIcon::Grab);
@@ -463,60 +2175,302 @@ impl Project {
     }
 
     pub fn save(&self) -> Result<()> {
+        if self.save_blocked {
+            anyhow::bail!("state file could not be read, refusing to overwrite it");
+        }
+
         let path = self.state_file()?;
+        let content = ron::ser::to_string_pretty(&self.state, PrettyConfig::new())?;
 
-        if let Ok(f) = File::create(path) {
-            ron::ser::to_writer_pretty(BufWriter::new(f), &self.state, PrettyConfig::new())?;
+        if std::fs::read_to_string(&path)
+            .map(|current| current == content)
+            .unwrap_or_default()
+        {
+            return Ok(());
         }
+
+        backup::write_atomic(&path, content.as_bytes())?;
+        backup::create(&self.backup_dir()?, &content, false)?;
+
+        Ok(())
+    }
+
+    fn restore_backup(&mut self, path: &Path) -> Result<()> {
+        let mut restored = backup::read(path)?;
+
+        let current = ron::ser::to_string_pretty(&self.state, PrettyConfig::new())?;
+        backup::create(&self.backup_dir()?, &current, true)?;
+
+        tracing::info!("restore backup: {:?}", path);
+
+        restored.format = self.state.format;
+        self.state = restored;
+        self.state.table_layout.retain_columns(self.max_cells);
+        self.history.clear();
+
+        self.update_row_ids();
+        self.migrate_indexed_meta_data();
+        self.reread_files();
+        self.update_visible_rows();
+        self.update_summary();
+
         Ok(())
     }
 
+    const TEMPLATE_PREVIEW_ROWS: usize = 5;
+
+    /// `(current name, new name)` of the first assigned rows, or of a sample
+    fn template_preview(&self, template: &str) -> Vec<(String, Result<String>)> {
+        let mut preview: Vec<_> = self
+            .transactions
+            .iter()
+            .enumerate()
+            .filter_map(|(idx, t)| Some((idx, t.as_ref().ok()?, self.meta(idx)?)))
+            .filter_map(|(idx, t, meta)| Some((idx, t, meta, meta.receipts.first()?)))
+            .take(Self::TEMPLATE_PREVIEW_ROWS)
+            .map(|(idx, t, meta, receipt)| {
+                let current = receipt.file_name().to_string();
+                let original = receipt.original_name.as_deref().unwrap_or(&current);
+                let ctx = TemplateContext {
+                    index: idx,
+                    transaction: t,
+                    category: meta.category.as_deref().unwrap_or_default(),
+                    original: Path::new(original)
+                        .file_stem()
+                        .and_then(|f| f.to_str())
+                        .unwrap_or_default(),
+                };
+                let new = template::render(template, &ctx);
+                (current, new)
+            })
+            .collect();
+
+        if preview.is_empty() {
+            let sample = Transaction {
+                date: chrono::NaiveDate::from_ymd_opt(2022, 6, 15).unwrap_or_default(),
+                amount: transaction::Amount::parse("-12,34").unwrap_or_default(),
+                currency: self.format.currency().to_string(),
+                description: "Sample Shop".to_string(),
+            };
+            let ctx = TemplateContext {
+                index: 1,
+                transaction: &sample,
+                category: "Travel",
+                original: "invoice-4711",
+            };
+            preview.push(("invoice-4711".to_string(), template::render(template, &ctx)));
+        }
+
+        preview
+    }
+
+    fn draw_backups(&mut self, ctx: &egui::Context) {
+        let Some(backups) = self.backups.as_ref() else {
+            return;
+        };
+
+        let mut open = true;
+        let mut restore = None;
+        egui::Window::new("Restore Backup")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                if backups.is_empty() {
+                    ui.label("no backups yet");
+                }
+
+                egui::Grid::new("backups").striped(true).show(ui, |ui| {
+                    for backup in backups {
+                        ui.label(
+                            backup
+                                .timestamp
+                                .with_timezone(&chrono::Local)
+                                .format("%Y-%m-%d %H:%M:%S")
+                                .to_string(),
+                        );
+                        ui.label(format!("{} assigned", backup.assigned));
+                        if ui.button("restore").clicked() {
+                            restore = Some(backup.path.clone());
+                        }
+                        ui.end_row();
+                    }
+                });
+            });
+
+        if let Some(path) = restore {
+            if let Err(e) = self.restore_backup(&path) {
+                tracing::error!("restore error: {}", e);
+            }
+            open = false;
+        }
+
+        if !open {
+            self.backups = None;
+        }
+    }
+
     pub fn load(&mut self) -> Result<()> {
         let path = self.state_file()?;
-        if let Ok(f) = File::open(path.clone()) {
-            tracing::info!("load state: {:?}", path);
-            if let Ok(state) = ron::de::from_reader(BufReader::new(f)) {
+        let content = match std::fs::read_to_string(&path) {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(()),
+            content => content,
+        };
+
+        tracing::info!("load state: {:?}", path);
+
+        match content
+            .map_err(anyhow::Error::from)
+            .and_then(|content| state::from_str(&content))
+        {
+            Ok(state) => {
                 self.state = state;
+                self.history.clear();
             }
-        }
+            Err(e) => {
+                tracing::error!("state invalid: {:#}", e);
+
+                // without a copy starting fresh would overwrite the only one
+                let backup = path.with_extension("ron.bak");
+                let copy = match std::fs::copy(&path, &backup) {
+                    Ok(_) => {
+                        self.state_copied = true;
+                        format!("A copy was kept as '{}'.", backup.display())
+                    }
+                    Err(copy_error) => {
+                        tracing::error!("state copy failed: {}", copy_error);
+                        self.state_copied = false;
+                        format!("No copy could be kept: {copy_error}")
+                    }
+                };
 
-        //if mismatch in length we regenerate meta data
-        if self.state.row_meta_data.len() < self.rows.len() {
-            self.state.row_meta_data = vec![RowMetaData::default(); self.rows.len()];
+                self.save_blocked = true;
+                self.load_error = Some(format!(
+                    "'{}' could not be read:\n{:#}\n\n{}",
+                    path.display(),
+                    e,
+                    copy
+                ));
+            }
         }
 
         Ok(())
     }
 
+    fn draw_load_error(&mut self, ctx: &egui::Context) {
+        let Some(error) = self.load_error.as_ref() else {
+            return;
+        };
+
+        let mut close = false;
+        egui::Window::new("State could not be loaded")
+            .collapsible(false)
+            .resizable(false)
+            .show(ctx, |ui| {
+                ui.label(error);
+                ui.separator();
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(self.state_copied, egui::Button::new("Start fresh"))
+                        .on_disabled_hover_text("the file could not be copied, move it away first")
+                        .clicked()
+                    {
+                        self.save_blocked = false;
+                        close = true;
+                    }
+                    if ui.button("Keep file untouched").clicked() {
+                        close = true;
+                    }
+                });
+            });
+
+        if close {
+            self.load_error = None;
+        }
+    }
+
+    fn save_mapping(&self) -> Result<()> {
+        let content = ron::ser::to_string_pretty(&self.columns, PrettyConfig::new())?;
+        backup::write_atomic(&self.mapping_file()?, content.as_bytes())
+    }
+
+    fn load_mapping(&self) -> Result<Option<ColumnMapping>> {
+        let path = self.mapping_file()?;
+        if let Ok(f) = File::open(path.clone()) {
+            tracing::info!("load mapping: {:?}", path);
+            return Ok(ron::de::from_reader(BufReader::new(f)).ok());
+        }
+
+        Ok(None)
+    }
+
     fn state_file(&self) -> Result<PathBuf> {
+        self.project_file("state.ron")
+    }
+
+    fn mapping_file(&self) -> Result<PathBuf> {
+        self.project_file("mapping.ron")
+    }
+
+    fn backup_dir(&self) -> Result<PathBuf> {
+        Ok(self.project_file(".ccaccounting")?.join("backups"))
+    }
+
+    fn ocr_cache_dir(&self) -> Result<PathBuf> {
+        Ok(self.project_file(".ccaccounting")?.join("ocr"))
+    }
+
+    /// images converted to pdf are kept here so the conversion can be undone
+    fn converted_dir(&self) -> Result<PathBuf> {
+        Ok(self.project_file(".ccaccounting")?.join("converted"))
+    }
+
+    fn project_file(&self, name: &str) -> Result<PathBuf> {
         Ok(self
             .input_file
             .parent()
             .ok_or_else(|| anyhow::anyhow!("parent path of input invalid"))?
-            .join("state.ron"))
+            .join(name))
     }
 
     pub(crate) fn check_shortcuts(&mut self, ui: &Ui) {
         if ui.input_mut(|input| input.consume_shortcut(&self.shortcut_reread_files)) {
-            self.reread_pdfs();
+            self.reread_files();
+        }
+        if ui.input_mut(|input| input.consume_shortcut(&self.shortcut_redo)) {
+            self.redo();
+        }
+        if ui.input_mut(|input| input.consume_shortcut(&self.shortcut_undo)) {
+            self.undo();
         }
     }
 }
 
-fn find_pdfs(path: &Path) -> Vec<PathBuf> {
-    let paths = std::fs::read_dir(path).unwrap();
+fn open_receipt(path: &Path) {
+    let kind = ReceiptKind::from_path(path).unwrap_or(ReceiptKind::Pdf);
+    if let Err(e) = kind.open(path) {
+        tracing::error!("open error: {}", e);
+    }
+}
 
-    let mut res = Vec::new();
-    for path in paths {
-        let path = path.unwrap().path();
-        if path
-            .extension()
-            .map(|ext| ext.to_ascii_lowercase() == "pdf")
-            .unwrap_or_default()
-        {
-            res.push(path.to_path_buf());
-        }
+/// text field of the filter bar, returns true if it changed
+fn filter_input(ui: &mut Ui, text: &mut String, hint: &str, invalid: bool) -> bool {
+    let mut edit = egui::TextEdit::singleline(text)
+        .hint_text(hint)
+        .desired_width(80.0);
+    if invalid {
+        edit = edit.text_color(Color32::RED);
     }
+    ui.add(edit).changed()
+}
 
-    res
+/// toggles the visibility of every csv column
+fn columns_menu(ui: &mut Ui, layout: &mut TableLayout, names: &[String]) {
+    ui.menu_button("columns", |ui| {
+        for column in layout.ordered(names.len()) {
+            let mut visible = !layout.is_hidden(column);
+            if ui.checkbox(&mut visible, &names[column]).changed() {
+                layout.set_hidden(column, !visible);
+            }
+        }
+    });
 }
@@ -0,0 +1,260 @@
+use anyhow::Result;
+use regex::Regex;
+use std::{path::Path, sync::OnceLock};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, serde::Deserialize, serde::Serialize)]
+pub enum ReceiptKind {
+    Pdf,
+    Image,
+    Email,
+    Html,
+    Office,
+}
+
+impl ReceiptKind {
+    pub const ALL: [ReceiptKind; 5] = [
+        ReceiptKind::Pdf,
+        ReceiptKind::Image,
+        ReceiptKind::Email,
+        ReceiptKind::Html,
+        ReceiptKind::Office,
+    ];
+
+    pub fn name(self) -> &'static str {
+        match self {
+            ReceiptKind::Pdf => "PDF",
+            ReceiptKind::Image => "Images",
+            ReceiptKind::Email => "E-Mails",
+            ReceiptKind::Html => "HTML",
+            ReceiptKind::Office => "Office Documents",
+        }
+    }
+
+    pub fn extensions(self) -> &'static [&'static str] {
+        match self {
+            ReceiptKind::Pdf => &["pdf"],
+            ReceiptKind::Image => &["jpg", "jpeg", "png"],
+            ReceiptKind::Email => &["eml"],
+            ReceiptKind::Html => &["html", "htm"],
+            ReceiptKind::Office => &["docx", "xlsx", "odt", "ods"],
+        }
+    }
+
+    /// shown instead of a thumbnail
+    pub fn icon(self) -> &'static str {
+        match self {
+            ReceiptKind::Pdf => "📄",
+            ReceiptKind::Image => "🖼",
+            ReceiptKind::Email => "✉",
+            ReceiptKind::Html => "🌐",
+            ReceiptKind::Office => "📝",
+        }
+    }
+
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let ext = path.extension()?.to_str()?;
+        Self::ALL.into_iter().find(|kind| {
+            kind.extensions()
+                .iter()
+                .any(|e| e.eq_ignore_ascii_case(ext))
+        })
+    }
+
+    /// kinds that may not contain a text layer and are worth running OCR on
+    #[cfg(feature = "ocr")]
+    pub fn needs_ocr(self) -> bool {
+        matches!(self, ReceiptKind::Pdf | ReceiptKind::Image)
+    }
+
+    pub fn open(self, path: &Path) -> Result<()> {
+        match self {
+            ReceiptKind::Html => opener::open_browser(path)?,
+            _ => opener::open(path)?,
+        }
+        Ok(())
+    }
+
+    /// text of the receipt without OCR, empty if there is no text layer
+    pub fn extract_text(self, path: &Path) -> Result<String> {
+        match self {
+            ReceiptKind::Pdf => crate::extract::pdf_text(path),
+            ReceiptKind::Image => Ok(String::new()),
+            ReceiptKind::Email => email_text(path),
+            ReceiptKind::Office => office_text(path),
+            ReceiptKind::Html => Ok(strip_html(&std::fs::read_to_string(path)?)),
+        }
+    }
+}
+
+fn email_text(path: &Path) -> Result<String> {
+    let content = std::fs::read(path)?;
+    let mail = mail_parser::MessageParser::default()
+        .parse(&content)
+        .ok_or_else(|| anyhow::anyhow!("invalid e-mail"))?;
+
+    let mut res = Vec::new();
+    if let Some(from) = mail.from().and_then(|from| from.first()) {
+        res.extend(from.name().map(str::to_string));
+        res.extend(from.address().map(str::to_string));
+    }
+    res.extend(mail.subject().map(str::to_string));
+    res.extend(mail.date().map(|date| date.to_rfc3339()));
+
+    // html only mails are converted to text by the parser
+    for idx in 0..mail.text_body_count() {
+        res.extend(mail.body_text(idx).map(|text| text.to_string()));
+    }
+
+    Ok(res.join("\n"))
+}
+
+fn strip_html(html: &str) -> String {
+    static BLOCKS: OnceLock<Regex> = OnceLock::new();
+    static TAGS: OnceLock<Regex> = OnceLock::new();
+
+    let blocks = BLOCKS.get_or_init(|| {
+        Regex::new(r"(?is)<(script|style)[^>]*>.*?</(script|style)>").expect("valid regex")
+    });
+    let tags = TAGS.get_or_init(|| Regex::new(r"(?s)<[^>]*>").expect("valid regex"));
+
+    let text = blocks.replace_all(html, " ");
+    let text = tags.replace_all(&text, " ");
+
+    decode_entities(&text)
+}
+
+/// docx, xlsx, odt and ods are zips of xml files, the text is read from those
+fn office_text(path: &Path) -> Result<String> {
+    static BREAKS: OnceLock<Regex> = OnceLock::new();
+    static TAGS: OnceLock<Regex> = OnceLock::new();
+
+    // words may be split over several runs, so only paragraph and cell ends separate them
+    let breaks = BREAKS.get_or_init(|| {
+        Regex::new(
+            r"</(w:p|w:tc|si|c|text:p|text:h|table:table-cell)>|<(w:tab|w:br|text:tab|text:s|text:line-break)\b[^>]*/>",
+        )
+        .expect("valid regex")
+    });
+    let tags = TAGS.get_or_init(|| Regex::new(r"(?s)<[^>]*>").expect("valid regex"));
+
+    let mut archive = zip::ZipArchive::new(std::fs::File::open(path)?)?;
+    let mut names: Vec<String> = archive
+        .file_names()
+        .filter(|name| {
+            *name == "word/document.xml"
+                || *name == "xl/sharedStrings.xml"
+                || (name.starts_with("xl/worksheets/") && name.ends_with(".xml"))
+                || *name == "content.xml"
+        })
+        .map(str::to_string)
+        .collect();
+    names.sort();
+
+    let mut res = Vec::new();
+    for name in names {
+        let mut xml = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name(&name)?, &mut xml)?;
+
+        let text = breaks.replace_all(&xml, " ");
+        res.push(decode_entities(&tags.replace_all(&text, "")));
+    }
+
+    Ok(res.join("\n"))
+}
+
+fn decode_entities(text: &str) -> String {
+    text.replace("&nbsp;", " ")
+        .replace("&euro;", "€")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&quot;", "\"")
+        .replace("&amp;", "&")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdir::TestDir;
+    use std::io::Write;
+
+    /// writes a zip with the given entries into `dir`
+    fn office_file(dir: &Path, name: &str, entries: &[(&str, &str)]) -> std::path::PathBuf {
+        let path = dir.join(name);
+        let mut zip = zip::ZipWriter::new(std::fs::File::create(&path).unwrap());
+        for (entry, content) in entries {
+            zip.start_file(*entry, zip::write::FileOptions::default())
+                .unwrap();
+            zip.write_all(content.as_bytes()).unwrap();
+        }
+        zip.finish().unwrap();
+        path
+    }
+
+    fn text(path: &Path) -> String {
+        let kind = ReceiptKind::from_path(path).unwrap();
+        let text = kind.extract_text(path).unwrap();
+        text.split_whitespace().collect::<Vec<_>>().join(" ")
+    }
+
+    #[test]
+    fn docx_text() {
+        let dir = TestDir::new("office");
+        let path = office_file(
+            &dir,
+            "invoice.docx",
+            &[
+                ("[Content_Types].xml", "<Types/>"),
+                (
+                    "word/document.xml",
+                    r#"<w:document><w:body><w:p><w:r><w:t>Rech</w:t></w:r><w:r><w:t xml:space="preserve">nung</w:t></w:r></w:p><w:p><w:r><w:t>Summe</w:t><w:tab/><w:t>12,34 &amp; mehr</w:t></w:r></w:p></w:body></w:document>"#,
+                ),
+            ],
+        );
+        assert_eq!(text(&path), "Rechnung Summe 12,34 & mehr");
+    }
+
+    #[test]
+    fn xlsx_text() {
+        let dir = TestDir::new("office");
+        let path = office_file(
+            &dir,
+            "invoice.xlsx",
+            &[
+                (
+                    "xl/sharedStrings.xml",
+                    r#"<sst><si><t>Total</t></si><si><t>EUR</t></si></sst>"#,
+                ),
+                (
+                    "xl/worksheets/sheet1.xml",
+                    r#"<worksheet><sheetData><row><c t="s"><v>0</v></c><c><v>99.5</v></c></row></sheetData></worksheet>"#,
+                ),
+            ],
+        );
+        assert_eq!(text(&path), "Total EUR 0 99.5");
+    }
+
+    #[test]
+    fn odt_text() {
+        let dir = TestDir::new("office");
+        let path = office_file(
+            &dir,
+            "invoice.odt",
+            &[
+                ("mimetype", "application/vnd.oasis.opendocument.text"),
+                (
+                    "content.xml",
+                    r#"<office:document-content><office:body><text:p>Hotel<text:s/>Berlin</text:p><text:p>42,00 &euro;</text:p></office:body></office:document-content>"#,
+                ),
+            ],
+        );
+        assert_eq!(text(&path), "Hotel Berlin 42,00 €");
+    }
+
+    #[test]
+    fn invalid_office_file() {
+        let dir = TestDir::new("office");
+        let path = dir.join("broken.docx");
+        std::fs::write(&path, "not a zip").unwrap();
+        assert!(ReceiptKind::Office.extract_text(&path).is_err());
+    }
+}
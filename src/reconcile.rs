@@ -0,0 +1,431 @@
+use anyhow::Result;
+use chrono::NaiveDate;
+use egui::{Color32, Grid, TextEdit, Ui};
+use std::collections::HashMap;
+
+use crate::{
+    project::CsvRow,
+    transaction::{Amount, Transaction},
+};
+
+/// balances of the statement, entered by hand or read from the csv
+#[derive(Debug, Clone, Copy, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct Balances {
+    pub opening: Option<Amount>,
+    pub closing: Option<Amount>,
+    /// amount that was charged to the bank account
+    pub settlement: Option<Amount>,
+}
+
+impl Balances {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// balances listed above the header, e.g. `Saldo:;-553,92 EUR`
+    pub fn from_preamble(records: &[CsvRow]) -> Self {
+        let mut res = Self::default();
+        for row in records {
+            let label = row.cell(Some(0)).to_lowercase();
+            let Some(amount) = row.cells.iter().skip(1).find_map(|c| Amount::parse(c).ok()) else {
+                continue;
+            };
+
+            if ["opening", "anfang", "alter saldo", "previous"]
+                .iter()
+                .any(|l| label.contains(l))
+            {
+                res.opening = Some(amount);
+            } else if ["saldo", "balance"].iter().any(|l| label.contains(l)) {
+                res.closing = Some(amount);
+            }
+        }
+        res
+    }
+}
+
+/// outcome of comparing the rows against the balances
+#[derive(Debug, Default)]
+pub struct Reconciliation {
+    pub balances: Balances,
+    /// sum of all rows with the sign the balances use
+    pub sum: Amount,
+    /// closing minus opening balance minus the sum of the rows
+    pub balance_gap: Option<Amount>,
+    /// settlement minus the closing balance
+    pub settlement_gap: Option<Amount>,
+    /// rows with the same date, amount and description as an earlier row
+    pub duplicates: Vec<usize>,
+    /// rows with the amount of the gap, one may be counted twice or have a missing twin
+    pub gap_rows: Vec<usize>,
+    /// rows left out of the sum
+    pub unparsed: Vec<usize>,
+}
+
+impl Reconciliation {
+    pub fn new(balances: Balances, transactions: &[Result<Transaction>]) -> Self {
+        let mut res = Self {
+            balances,
+            ..Default::default()
+        };
+
+        let mut seen = HashMap::<(NaiveDate, Amount, &str), usize>::new();
+        for (idx, transaction) in transactions.iter().enumerate() {
+            let Ok(t) = transaction else {
+                res.unparsed.push(idx);
+                continue;
+            };
+            res.sum = res.sum + t.amount;
+
+            let count = seen
+                .entry((t.date, t.amount, t.description.as_str()))
+                .or_default();
+            if *count > 0 {
+                res.duplicates.push(idx);
+            }
+            *count += 1;
+        }
+
+        let opening = balances.opening.unwrap_or_default();
+        if let Some(closing) = balances.closing {
+            let expected = closing - opening;
+            // banks may keep the balance with the opposite sign of the rows
+            if (expected + res.sum).abs() < (expected - res.sum).abs() {
+                res.sum = -res.sum;
+            }
+            res.balance_gap = Some(expected - res.sum);
+        }
+
+        if let Some(settlement) = balances.settlement {
+            let charged = balances.closing.unwrap_or(opening + res.sum);
+            res.settlement_gap = Some(settlement.abs() - charged.abs());
+        }
+
+        if let Some(gap) = res.balance_gap.filter(|gap| *gap != Amount::ZERO) {
+            res.gap_rows = transactions
+                .iter()
+                .enumerate()
+                .filter(|(_, t)| t.as_ref().is_ok_and(|t| t.amount.abs() == gap.abs()))
+                .map(|(idx, _)| idx)
+                .collect();
+        }
+
+        res
+    }
+
+    /// `None` until balances were entered
+    pub fn is_reconciled(&self) -> Option<bool> {
+        if self.balances.is_empty() {
+            return None;
+        }
+
+        Some(
+            [self.balance_gap, self.settlement_gap]
+                .into_iter()
+                .flatten()
+                .all(|gap| gap == Amount::ZERO),
+        )
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct ReconcileWindow {
+    open: bool,
+    /// opening, closing and settlement
+    inputs: [String; 3],
+    /// balances found in the csv
+    imported: Balances,
+}
+
+impl ReconcileWindow {
+    pub fn open(&mut self, balances: Balances, imported: Balances) {
+        self.inputs = [balances.opening, balances.closing, balances.settlement]
+            .map(|b| b.map(|b| b.to_string()).unwrap_or_default());
+        self.imported = imported;
+        self.open = true;
+    }
+
+    fn parsed(&self) -> [Result<Option<Amount>>; 3] {
+        self.inputs.clone().map(|input| {
+            let input = input.trim();
+            if input.is_empty() {
+                return Ok(None);
+            }
+            Amount::parse(input).map(Some)
+        })
+    }
+
+    /// returns the balances once the user applied them
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        reconciliation: &Reconciliation,
+    ) -> Option<Balances> {
+        let mut result = None;
+        let mut open = self.open;
+
+        egui::Window::new("Reconciliation")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                let parsed = self.parsed();
+
+                Grid::new("balances").show(ui, |ui| {
+                    let labels = ["Opening balance", "Closing balance", "Settlement amount"];
+                    for ((label, input), parsed) in
+                        labels.iter().zip(self.inputs.iter_mut()).zip(parsed.iter())
+                    {
+                        ui.label(*label);
+                        let mut edit = TextEdit::singleline(input)
+                            .hint_text("12,34")
+                            .desired_width(100.0);
+                        if parsed.is_err() {
+                            edit = edit.text_color(Color32::RED);
+                        }
+                        ui.add(edit);
+                        ui.end_row();
+                    }
+                });
+
+                ui.horizontal(|ui| {
+                    if ui
+                        .add_enabled(
+                            !self.imported.is_empty(),
+                            egui::Button::new("Import from Statement"),
+                        )
+                        .on_disabled_hover_text("the csv lists no balances")
+                        .clicked()
+                    {
+                        let imported = [
+                            self.imported.opening,
+                            self.imported.closing,
+                            self.imported.settlement,
+                        ];
+                        for (input, imported) in self.inputs.iter_mut().zip(imported) {
+                            if let Some(amount) = imported {
+                                *input = amount.to_string();
+                            }
+                        }
+                    }
+
+                    if let [Ok(opening), Ok(closing), Ok(settlement)] = parsed {
+                        if ui.button("Apply").clicked() {
+                            result = Some(Balances {
+                                opening,
+                                closing,
+                                settlement,
+                            });
+                        }
+                    } else {
+                        ui.add_enabled(false, egui::Button::new("Apply"));
+                    }
+                });
+
+                ui.separator();
+                draw_result(ui, reconciliation);
+            });
+
+        self.open = open;
+
+        result
+    }
+}
+
+fn draw_result(ui: &mut Ui, reconciliation: &Reconciliation) {
+    let rows = |rows: &[usize]| {
+        rows.iter()
+            .map(|idx| format!("{idx:0>3}"))
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+
+    ui.label(format!("sum of rows: {}", reconciliation.sum));
+
+    match reconciliation.is_reconciled() {
+        None => {
+            ui.weak("enter the balances of the statement to check it is complete");
+        }
+        Some(true) => {
+            ui.colored_label(Color32::DARK_GREEN, "✔ the rows match the statement");
+        }
+        Some(false) => {
+            if let Some(gap) = reconciliation.balance_gap.filter(|g| *g != Amount::ZERO) {
+                ui.colored_label(
+                    Color32::RED,
+                    format!("⚠ the balances differ from the rows by {gap}"),
+                );
+            }
+            if let Some(gap) = reconciliation.settlement_gap.filter(|g| *g != Amount::ZERO) {
+                ui.colored_label(
+                    Color32::RED,
+                    format!("⚠ the settlement differs from the balance by {gap}"),
+                );
+            }
+            if !reconciliation.gap_rows.is_empty() {
+                ui.label(format!(
+                    "rows with the amount of the gap (counted twice or missing a twin): {}",
+                    rows(&reconciliation.gap_rows)
+                ));
+            }
+        }
+    }
+
+    if !reconciliation.duplicates.is_empty() {
+        ui.colored_label(
+            Color32::YELLOW,
+            format!(
+                "rows repeating an earlier row: {}",
+                rows(&reconciliation.duplicates)
+            ),
+        );
+    }
+    if !reconciliation.unparsed.is_empty() {
+        ui.colored_label(
+            Color32::RED,
+            format!(
+                "rows without amount, not part of the sum: {}",
+                rows(&reconciliation.unparsed)
+            ),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn amount(input: &str) -> Amount {
+        Amount::parse(input).unwrap()
+    }
+
+    fn transaction(day: u32, value: &str, description: &str) -> Result<Transaction> {
+        Ok(Transaction {
+            date: NaiveDate::from_ymd_opt(2023, 1, day).unwrap(),
+            amount: amount(value),
+            currency: "EUR".to_string(),
+            description: description.to_string(),
+        })
+    }
+
+    fn rows() -> Vec<Result<Transaction>> {
+        vec![
+            transaction(2, "-10,00", "Coffee"),
+            transaction(3, "-25,50", "Hotel"),
+            transaction(4, "-10,00", "Lunch"),
+            transaction(5, "5,50", "Refund"),
+        ]
+    }
+
+    fn balances(opening: &str, closing: &str) -> Balances {
+        Balances {
+            opening: Some(amount(opening)),
+            closing: Some(amount(closing)),
+            settlement: None,
+        }
+    }
+
+    fn preamble(rows: &[&[&str]]) -> Vec<CsvRow> {
+        rows.iter()
+            .map(|cells| CsvRow {
+                cells: cells.iter().map(|c| c.to_string()).collect(),
+            })
+            .collect()
+    }
+
+    #[test]
+    fn balances_from_preamble() {
+        let balances = Balances::from_preamble(&preamble(&[
+            &["Kartennummer:", "1234"],
+            &["Neuer Saldo:", "-553,92 EUR"],
+            // contains `saldo` too but is the opening balance
+            &["Alter Saldo:", "-100,00 EUR"],
+            &["Datum", "Beschreibung", "Betrag"],
+        ]));
+        assert_eq!(balances.opening, Some(amount("-100")));
+        assert_eq!(balances.closing, Some(amount("-553,92")));
+        assert_eq!(balances.settlement, None);
+
+        let balances = Balances::from_preamble(&preamble(&[
+            &["Opening balance", "", "12.00"],
+            &["Balance", "n/a"],
+        ]));
+        assert_eq!(balances.opening, Some(amount("12")));
+        assert_eq!(balances.closing, None);
+        assert!(Balances::from_preamble(&[]).is_empty());
+    }
+
+    #[test]
+    fn complete_rows() {
+        let res = Reconciliation::new(balances("0", "-40"), &rows());
+        assert_eq!(res.sum, amount("-40"));
+        assert_eq!(res.balance_gap, Some(Amount::ZERO));
+        assert!(res.gap_rows.is_empty());
+        assert_eq!(res.is_reconciled(), Some(true));
+
+        // no balances, nothing to tell
+        let res = Reconciliation::new(Balances::default(), &rows());
+        assert_eq!(res.balance_gap, None);
+        assert_eq!(res.is_reconciled(), None);
+    }
+
+    #[test]
+    fn opposite_sign() {
+        // the card balance counts what is owed as positive
+        let res = Reconciliation::new(balances("100", "140"), &rows());
+        assert_eq!(res.sum, amount("40"));
+        assert_eq!(res.balance_gap, Some(Amount::ZERO));
+        assert_eq!(res.is_reconciled(), Some(true));
+    }
+
+    #[test]
+    fn deleted_row() {
+        let mut rows = rows();
+        let removed = rows.remove(2);
+
+        let res = Reconciliation::new(balances("0", "-40"), &rows);
+        assert_eq!(res.balance_gap, Some(amount("-10")));
+        assert_eq!(res.is_reconciled(), Some(false));
+        // the twin of the deleted row is pointed out
+        assert_eq!(res.gap_rows, vec![0]);
+        assert_eq!(
+            rows[res.gap_rows[0]].as_ref().unwrap().amount,
+            removed.unwrap().amount
+        );
+    }
+
+    #[test]
+    fn settlement() {
+        let mut balances = balances("0", "-40");
+        balances.settlement = Some(amount("40"));
+        let res = Reconciliation::new(balances, &rows());
+        assert_eq!(res.settlement_gap, Some(Amount::ZERO));
+        assert_eq!(res.is_reconciled(), Some(true));
+
+        balances.settlement = Some(amount("35"));
+        let res = Reconciliation::new(balances, &rows());
+        assert_eq!(res.settlement_gap, Some(amount("-5")));
+        assert_eq!(res.is_reconciled(), Some(false));
+
+        // without a closing balance the rows are charged
+        let balances = Balances {
+            settlement: Some(amount("40")),
+            ..Default::default()
+        };
+        let res = Reconciliation::new(balances, &rows());
+        assert_eq!(res.settlement_gap, Some(Amount::ZERO));
+    }
+
+    #[test]
+    fn duplicates_and_unparsed() {
+        let mut rows = rows();
+        rows.push(transaction(3, "-25,50", "Hotel"));
+        rows.push(Err(anyhow::anyhow!("invalid amount")));
+        rows.push(transaction(3, "-25,50", "Hotel"));
+
+        let res = Reconciliation::new(Balances::default(), &rows);
+        assert_eq!(res.duplicates, vec![4, 6]);
+        assert_eq!(res.unparsed, vec![5]);
+        assert_eq!(res.sum, amount("-91"));
+    }
+}
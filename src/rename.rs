@@ -0,0 +1,382 @@
+use egui::{Button, Color32, Grid, ScrollArea};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+};
+
+use crate::history::Command;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RenameIssue {
+    /// another rename in the batch has the same target
+    Collision(usize),
+    MissingSource,
+    /// a file not part of the batch already has the target name
+    TargetExists,
+    /// renames in the batch would need to swap names
+    Cycle,
+    /// the listed characters are replaced by `_`
+    InvalidCharacters(Vec<char>),
+}
+
+impl RenameIssue {
+    /// only invalid characters are fixed up automatically
+    pub fn is_blocking(&self) -> bool {
+        !matches!(self, RenameIssue::InvalidCharacters(_))
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            RenameIssue::Collision(idx) => format!("same name as row {idx:0>3}"),
+            RenameIssue::MissingSource => "file missing".to_string(),
+            RenameIssue::TargetExists => "target exists".to_string(),
+            RenameIssue::Cycle => "names would need to be swapped".to_string(),
+            RenameIssue::InvalidCharacters(chars) => {
+                format!("replaced: {}", chars.iter().collect::<String>())
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenameOp {
+    pub row: String,
+    pub index: usize,
+    pub from: String,
+    pub to: String,
+    pub issues: Vec<RenameIssue>,
+}
+
+impl RenameOp {
+    pub fn new(row: String, index: usize, from: String, to: String) -> Self {
+        Self {
+            row,
+            index,
+            from,
+            to,
+            issues: Vec::new(),
+        }
+    }
+
+    pub fn is_blocked(&self) -> bool {
+        self.issues.iter().any(RenameIssue::is_blocking)
+    }
+}
+
+/// checks all renames for issues and orders them so no rename overwrites a pending source
+pub fn plan(mut ops: Vec<RenameOp>) -> Vec<RenameOp> {
+    ops.retain(|op| op.from != op.to);
+
+    let mut targets = HashMap::<String, Vec<usize>>::new();
+    for op in &ops {
+        targets
+            .entry(op.to.to_lowercase())
+            .or_default()
+            .push(op.index);
+    }
+
+    for op in &mut ops {
+        if !Path::new(&op.from).exists() {
+            op.issues.push(RenameIssue::MissingSource);
+        }
+
+        if let Some(other) = targets[&op.to.to_lowercase()]
+            .iter()
+            .find(|idx| **idx != op.index)
+        {
+            op.issues.push(RenameIssue::Collision(*other));
+        }
+    }
+
+    // a target may only exist if it is moved away first, which blocked renames never do,
+    // blocking one rename can block the rename into its source in turn
+    loop {
+        let sources: Vec<String> = ops
+            .iter()
+            .filter(|op| !op.is_blocked())
+            .map(|op| op.from.to_lowercase())
+            .collect();
+
+        let mut changed = false;
+        for op in ops.iter_mut().filter(|op| !op.is_blocked()) {
+            let case_change_only = op.from.to_lowercase() == op.to.to_lowercase();
+            if Path::new(&op.to).exists()
+                && !case_change_only
+                && !sources.contains(&op.to.to_lowercase())
+            {
+                op.issues.push(RenameIssue::TargetExists);
+                changed = true;
+            }
+        }
+
+        if !changed {
+            break;
+        }
+    }
+
+    order(ops)
+}
+
+fn order(ops: Vec<RenameOp>) -> Vec<RenameOp> {
+    let (mut pending, blocked): (Vec<_>, Vec<_>) = ops.into_iter().partition(|op| !op.is_blocked());
+    let mut res = Vec::with_capacity(pending.len() + blocked.len());
+
+    while !pending.is_empty() {
+        let next = pending.iter().position(|op| {
+            !pending
+                .iter()
+                .any(|other| other.from.to_lowercase() == op.to.to_lowercase())
+        });
+
+        match next {
+            Some(idx) => res.push(pending.remove(idx)),
+            None => {
+                for mut op in pending.drain(..) {
+                    op.issues.push(RenameIssue::Cycle);
+                    res.push(op);
+                }
+            }
+        }
+    }
+
+    res.extend(blocked);
+    res
+}
+
+#[derive(Debug, Default)]
+pub struct RenameDialog {
+    ops: Option<Vec<RenameOp>>,
+    converted_dir: PathBuf,
+}
+
+impl RenameDialog {
+    /// `converted_dir` keeps images that are converted to pdf
+    pub fn open(&mut self, ops: Vec<RenameOp>, converted_dir: PathBuf) {
+        self.ops = Some(plan(ops));
+        self.converted_dir = converted_dir;
+    }
+
+    /// returns the batch of renames once the user applied them
+    pub fn show(&mut self, ctx: &egui::Context) -> Option<Command> {
+        let ops = self.ops.as_ref()?;
+
+        let mut result = None;
+        let mut open = true;
+        let mut cancel = false;
+        let valid = ops.iter().filter(|op| !op.is_blocked()).count();
+
+        egui::Window::new("Rename All Assigned")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                if ops.is_empty() {
+                    ui.label("all receipts are named correctly");
+                }
+
+                ScrollArea::both().max_height(400.0).show(ui, |ui| {
+                    Grid::new("rename_preview").striped(true).show(ui, |ui| {
+                        for op in ops {
+                            let color = if op.is_blocked() {
+                                Color32::RED
+                            } else {
+                                ui.visuals().text_color()
+                            };
+
+                            ui.label(format!("{:0>3}", op.index));
+                            ui.colored_label(color, file_name(&op.from));
+                            ui.label("→");
+                            ui.colored_label(color, file_name(&op.to));
+                            ui.label(
+                                op.issues
+                                    .iter()
+                                    .map(RenameIssue::describe)
+                                    .collect::<Vec<_>>()
+                                    .join(", "),
+                            );
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    let skipped = ops.len() - valid;
+                    let label = if skipped > 0 {
+                        format!("Rename {valid} files ({skipped} skipped)")
+                    } else {
+                        format!("Rename {valid} files")
+                    };
+                    if ui.add_enabled(valid > 0, Button::new(label)).clicked() {
+                        result = Some(Command::Batch(
+                            ops.iter()
+                                .filter(|op| !op.is_blocked())
+                                .map(|op| {
+                                    Command::rename(
+                                        &op.row,
+                                        op.from.clone(),
+                                        op.to.clone(),
+                                        &self.converted_dir,
+                                    )
+                                })
+                                .collect(),
+                        ));
+                    }
+                    if ui.button("Cancel").clicked() {
+                        cancel = true;
+                    }
+                });
+            });
+
+        if !open || cancel || result.is_some() {
+            self.ops = None;
+        }
+
+        result
+    }
+}
+
+fn file_name(path: &str) -> String {
+    Path::new(path)
+        .file_name()
+        .map(|f| f.to_string_lossy().to_string())
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::testdir::TestDir;
+
+    fn dir(name: &str, files: &[&str]) -> TestDir {
+        TestDir::with_files(&format!("rename-{name}"), files)
+    }
+
+    fn op(dir: &Path, index: usize, from: &str, to: &str) -> RenameOp {
+        RenameOp::new(
+            format!("row{index}"),
+            index,
+            dir.join(from).to_string_lossy().to_string(),
+            dir.join(to).to_string_lossy().to_string(),
+        )
+    }
+
+    /// renames in planned order with the issues of each
+    fn planned(ops: Vec<RenameOp>) -> Vec<(usize, Vec<RenameIssue>)> {
+        plan(ops)
+            .into_iter()
+            .map(|op| (op.index, op.issues))
+            .collect()
+    }
+
+    #[test]
+    fn plan_chain() {
+        let d = dir("chain", &["a", "b"]);
+        // `b` has to move away before `a` can take its name
+        let res = planned(vec![op(&d, 0, "a", "b"), op(&d, 1, "b", "c")]);
+        assert_eq!(res, vec![(1, vec![]), (0, vec![])]);
+    }
+
+    #[test]
+    fn plan_swap() {
+        let d = dir("swap", &["a", "b"]);
+        let res = planned(vec![op(&d, 0, "a", "b"), op(&d, 1, "b", "a")]);
+        assert_eq!(
+            res,
+            vec![(0, vec![RenameIssue::Cycle]), (1, vec![RenameIssue::Cycle])]
+        );
+    }
+
+    #[test]
+    fn plan_cycle() {
+        let d = dir("cycle", &["a", "b", "c", "x"]);
+        let res = planned(vec![
+            op(&d, 0, "a", "b"),
+            op(&d, 1, "b", "c"),
+            op(&d, 2, "c", "a"),
+            op(&d, 3, "x", "y"),
+        ]);
+        assert_eq!(res[0], (3, vec![]));
+        assert!(res[1..]
+            .iter()
+            .all(|(_, issues)| issues == &vec![RenameIssue::Cycle]));
+    }
+
+    #[test]
+    fn plan_collision() {
+        let d = dir("collision", &["a", "b"]);
+        let res = planned(vec![op(&d, 0, "a", "c"), op(&d, 1, "b", "C")]);
+        assert_eq!(
+            res,
+            vec![
+                (0, vec![RenameIssue::Collision(1)]),
+                (1, vec![RenameIssue::Collision(0)])
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_existing_and_missing() {
+        let d = dir("existing", &["a", "x"]);
+        let res = planned(vec![
+            op(&d, 0, "a", "x"),
+            op(&d, 1, "m", "n"),
+            op(&d, 2, "a", "a"),
+        ]);
+        assert_eq!(
+            res,
+            vec![
+                (0, vec![RenameIssue::TargetExists]),
+                (1, vec![RenameIssue::MissingSource])
+            ]
+        );
+    }
+
+    #[test]
+    fn plan_target_is_blocked_source() {
+        let d = dir("blocked", &["a", "b", "d"]);
+        // `b` stays where it is as its rename collides, so `a` cannot take its name
+        let res = planned(vec![
+            op(&d, 0, "a", "b"),
+            op(&d, 1, "b", "c"),
+            op(&d, 2, "d", "c"),
+        ]);
+        assert_eq!(res.len(), 3);
+        assert!(res
+            .iter()
+            .all(|(_, issues)| issues.iter().any(RenameIssue::is_blocking)));
+        assert!(res.contains(&(0, vec![RenameIssue::TargetExists])));
+    }
+
+    #[test]
+    fn plan_blocked_chain() {
+        let d = dir("blocked-chain", &["a", "b", "c", "z"]);
+        // `c` cannot move, so neither can `b` and then `a`
+        let res = planned(vec![
+            op(&d, 0, "a", "b"),
+            op(&d, 1, "b", "c"),
+            op(&d, 2, "c", "z"),
+        ]);
+        assert_eq!(res.len(), 3);
+        assert!(res
+            .iter()
+            .all(|(_, issues)| issues == &vec![RenameIssue::TargetExists]));
+    }
+
+    #[test]
+    fn order_without_files() {
+        let d = Path::new("/nonexistent");
+        let mut blocked = op(d, 3, "x", "y");
+        blocked.issues.push(RenameIssue::MissingSource);
+
+        let res = order(vec![
+            blocked,
+            op(d, 0, "a", "b"),
+            op(d, 1, "b", "c"),
+            op(d, 2, "c", "d"),
+        ]);
+        let indices: Vec<usize> = res.iter().map(|op| op.index).collect();
+        assert_eq!(indices, vec![2, 1, 0, 3]);
+        assert!(res[..3].iter().all(|op| op.issues.is_empty()));
+    }
+}
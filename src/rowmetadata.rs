@@ -1,62 +1,227 @@
-use crate::project::CsvRow;
+use crate::{
+    receipttype::ReceiptKind,
+    template::{self, TemplateContext},
+    transaction::{Amount, Transaction},
+};
 use std::path::Path;
 
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct AssignedReceipt {
+    pub path: String,
+    /// file name of the receipt before it was renamed
+    pub original_name: Option<String>,
+    /// part of the transaction amount this receipt covers
+    pub amount: Option<Amount>,
+}
+
+impl AssignedReceipt {
+    pub fn file_name(&self) -> &str {
+        Path::new(&self.path)
+            .file_name()
+            .and_then(|f| f.to_str())
+            .unwrap_or_default()
+    }
+
+    /// where the receipt would be with its original name, if it was renamed
+    pub fn original_path(&self) -> Option<String> {
+        let receipt = Path::new(&self.path);
+        let original = self.original_name.as_ref()?;
+        let original_path = receipt.parent()?.join(original);
+
+        // a receipt converted to pdf cannot get its image name back
+        if original_path.extension() != receipt.extension() {
+            return None;
+        }
+
+        (original_path != receipt).then(|| original_path.to_string_lossy().to_string())
+    }
+}
+
+/// how the amounts of the receipts of a row add up
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SplitStatus {
+    /// amounts add up to the transaction amount
+    Complete,
+    /// transaction amount minus the sum of the parts
+    Difference(Amount),
+    /// number of receipts without an amount
+    MissingAmounts(usize),
+}
+
 #[derive(Debug, Clone, Default, serde::Deserialize, serde::Serialize)]
 #[serde(default)]
 pub struct RowMetaData {
     pub hidden: bool,
-    pub receipt: Option<String>,
+    pub receipts: Vec<AssignedReceipt>,
+    pub category: Option<String>,
+    /// reason the row needs no receipt, e.g. a bank fee
+    pub no_receipt: Option<String>,
 }
 
 impl RowMetaData {
-    pub fn rename_pdf(&mut self, idx: usize, row: &CsvRow) {
-        let target_name = self.target_file_name(idx, row);
-        if let Some(receipt) = self.receipt.as_mut() {
-            let target_name = target_name.expect("cannot happen since receipt is not none");
+    /// adds the receipt as another part of the row
+    pub fn assign(&mut self, receipt: String) {
+        if self.receipts.iter().any(|r| r.path == receipt) {
+            return;
+        }
+
+        self.receipts.push(AssignedReceipt {
+            original_name: Path::new(&receipt)
+                .file_name()
+                .map(|f| f.to_string_lossy().to_string()),
+            path: receipt,
+            amount: None,
+        });
+    }
 
-            tracing::debug!("rename pdf: '{}' -> '{}'", receipt, target_name);
+    pub fn clear(&mut self) {
+        self.receipts.clear();
+    }
+
+    pub fn remove(&mut self, receipt: &str) {
+        self.receipts.retain(|r| r.path != receipt);
+    }
+
+    pub fn has_receipts(&self) -> bool {
+        !self.receipts.is_empty()
+    }
 
-            std::fs::rename(receipt.clone(), target_name.clone()).expect("TODO");
-            *receipt = target_name;
+    pub fn receipt_paths(&self) -> impl Iterator<Item = &str> {
+        self.receipts.iter().map(|r| r.path.as_str())
+    }
+
+    /// points the part at `from` to its new location
+    pub fn replace_receipt(&mut self, from: &str, to: &str) {
+        match self.receipts.iter_mut().find(|r| r.path == from) {
+            Some(receipt) => receipt.path = to.to_string(),
+            None => self.assign(to.to_string()),
         }
     }
 
-    pub fn is_name_correct(&self, idx: usize, row: &CsvRow) -> bool {
-        let target_name = self.target_file_name(idx, row);
-        if let Some(receipt) = self.receipt.as_ref() {
-            target_name.map(|f| f == *receipt).unwrap_or(false)
-        } else {
-            // no receipt means the name is correct
-            true
+    /// `None` for rows with a single receipt and no amount entered
+    pub fn split_status(&self, transaction: &Transaction) -> Option<SplitStatus> {
+        if self.receipts.len() < 2 && self.receipts.iter().all(|r| r.amount.is_none()) {
+            return None;
+        }
+
+        let missing = self.receipts.iter().filter(|r| r.amount.is_none()).count();
+        if missing > 0 {
+            return Some(SplitStatus::MissingAmounts(missing));
         }
+
+        let sum: Amount = self.receipts.iter().filter_map(|r| r.amount).sum();
+        let difference = transaction.amount.abs() - sum.abs();
+
+        Some(if difference == Amount::ZERO {
+            SplitStatus::Complete
+        } else {
+            SplitStatus::Difference(difference)
+        })
+    }
+
+    pub fn is_name_correct(
+        &self,
+        part: usize,
+        idx: usize,
+        transaction: &Transaction,
+        template: &str,
+        convert_images: bool,
+    ) -> bool {
+        self.target_file_name(part, idx, transaction, template, convert_images)
+            .is_some_and(|target| target == self.receipts[part].path)
     }
 
-    fn target_file_name(&self, idx: usize, row: &CsvRow) -> Option<String> {
-        if let Some(receipt) = self.receipt.as_ref() {
-            let receipt_path = Path::new(receipt);
-            let date = row.cells[0].clone();
-            //TODO: fix poor mans date format fix
-            let date = date.replace('/', ".");
-            let amount = row.cells[3].clone();
-            let entry_name = row.cells[2].clone().replace('/', "_");
-            let target_name = format!(
-                "{}/{:0>3}-{}{}EUR-{}.pdf",
-                receipt_path.parent().unwrap().to_str().unwrap(),
-                idx,
-                date,
-                amount,
-                entry_name,
-            );
-
-            Some(target_name)
+    /// rows with several receipts get the part number as suffix,
+    /// `convert_images` gives image receipts a pdf extension
+    pub fn target_file_name(
+        &self,
+        part: usize,
+        idx: usize,
+        transaction: &Transaction,
+        template: &str,
+        convert_images: bool,
+    ) -> Option<String> {
+        let receipt_path = Path::new(&self.receipts.get(part)?.path);
+
+        let name = self
+            .with_template_context(part, idx, transaction, |ctx| {
+                template::render(template, ctx).or_else(|e| {
+                    tracing::warn!("invalid template '{}': {:#}", template, e);
+                    template::render(template::DEFAULT_TEMPLATE, ctx)
+                })
+            })?
+            .ok()?;
+
+        let name = if self.receipts.len() > 1 {
+            format!("{}-{}", name, part + 1)
         } else {
-            None
-        }
+            name
+        };
+
+        let is_image = ReceiptKind::from_path(receipt_path) == Some(ReceiptKind::Image);
+        let file_name = match receipt_path.extension() {
+            Some(_) if convert_images && is_image => format!("{name}.pdf"),
+            Some(ext) => format!("{}.{}", name, ext.to_string_lossy()),
+            None => name,
+        };
+
+        Some(
+            receipt_path
+                .parent()?
+                .join(file_name)
+                .to_string_lossy()
+                .to_string(),
+        )
+    }
+
+    /// characters the template produced that had to be replaced in the target name
+    pub fn invalid_target_chars(
+        &self,
+        part: usize,
+        idx: usize,
+        transaction: &Transaction,
+        template: &str,
+    ) -> Vec<char> {
+        self.with_template_context(part, idx, transaction, |ctx| {
+            template::render_raw(template, ctx)
+                .map(|raw| template::invalid_chars(&raw))
+                .unwrap_or_default()
+        })
+        .unwrap_or_default()
+    }
+
+    fn with_template_context<R>(
+        &self,
+        part: usize,
+        idx: usize,
+        transaction: &Transaction,
+        f: impl FnOnce(&TemplateContext<'_>) -> R,
+    ) -> Option<R> {
+        let receipt = self.receipts.get(part)?;
+        let original = receipt
+            .original_name
+            .as_deref()
+            .map(Path::new)
+            .unwrap_or(Path::new(&receipt.path));
+
+        Some(f(&TemplateContext {
+            index: idx,
+            transaction,
+            category: self.category.as_deref().unwrap_or_default(),
+            original: &original
+                .file_stem()
+                .map(|f| f.to_string_lossy())
+                .unwrap_or_default(),
+        }))
     }
 
-    pub fn get_receipt_filename(&self) -> Option<&str> {
-        self.receipt
-            .as_ref()
-            .and_then(|r| Path::new(r).file_name().and_then(|f| f.to_str()))
+    /// file names of all receipts
+    pub fn receipt_file_names(&self) -> String {
+        self.receipts
+            .iter()
+            .map(AssignedReceipt::file_name)
+            .collect::<Vec<_>>()
+            .join(", ")
     }
 }
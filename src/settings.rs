@@ -0,0 +1,218 @@
+use anyhow::Result;
+use egui::{Button, Color32, Grid, TextEdit};
+use rfd::FileDialog;
+use std::{
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver},
+};
+
+use crate::{
+    discovery::{self, ReceiptFolder},
+    receipttype::ReceiptKind,
+    template::{self, PLACEHOLDERS},
+};
+
+/// settings stored per project
+#[derive(Debug, Clone, Default)]
+pub struct ProjectSettings {
+    pub template: String,
+    pub receipt_kinds: Vec<ReceiptKind>,
+    pub convert_images: bool,
+    pub receipt_folders: Vec<ReceiptFolder>,
+    pub ignore_patterns: Vec<String>,
+}
+
+#[derive(Debug, Default)]
+pub struct Settings {
+    open: bool,
+    settings: ProjectSettings,
+    /// ignore patterns, one per line
+    ignore_input: String,
+    wait_for_folder: Option<Receiver<Option<PathBuf>>>,
+}
+
+impl Settings {
+    pub fn open(&mut self, settings: ProjectSettings) {
+        self.ignore_input = settings.ignore_patterns.join("\n");
+        self.settings = settings;
+        self.open = true;
+    }
+
+    fn pick_folder(&mut self) {
+        let main = dispatch::Queue::main();
+
+        let (tx, rx) = channel();
+
+        main.exec_async(move || {
+            let path = FileDialog::new().pick_folder();
+
+            tx.send(path).unwrap_or_default();
+        });
+
+        self.wait_for_folder = Some(rx);
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.open
+    }
+
+    /// template currently being edited
+    pub fn template(&self) -> &str {
+        &self.settings.template
+    }
+
+    /// `preview` holds `(current name, new name)` for the edited template,
+    /// returns the settings once the user applied them
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        preview: &[(String, Result<String>)],
+    ) -> Option<ProjectSettings> {
+        let mut result = None;
+        let mut open = self.open;
+
+        if let Some(receiver) = self.wait_for_folder.as_ref() {
+            if let Ok(folder) = receiver.try_recv() {
+                if let Some(path) = folder {
+                    self.settings.receipt_folders.push(ReceiptFolder {
+                        path,
+                        recursive: true,
+                    });
+                }
+                self.wait_for_folder = None;
+            }
+        }
+
+        egui::Window::new("Settings")
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.label("Receipt file name template:");
+                ui.add(
+                    TextEdit::singleline(&mut self.settings.template).desired_width(f32::INFINITY),
+                );
+
+                ui.horizontal(|ui| {
+                    if ui.small_button("reset").clicked() {
+                        self.settings.template = template::DEFAULT_TEMPLATE.to_string();
+                    }
+                });
+
+                ui.collapsing("Placeholders", |ui| {
+                    Grid::new("placeholders").show(ui, |ui| {
+                        for (placeholder, description) in PLACEHOLDERS {
+                            ui.monospace(*placeholder);
+                            ui.label(*description);
+                            ui.end_row();
+                        }
+                    });
+                });
+
+                ui.separator();
+                ui.strong("Preview");
+
+                let mut valid = true;
+                Grid::new("template_preview").striped(true).show(ui, |ui| {
+                    for (current, new) in preview {
+                        ui.label(current);
+                        ui.label("→");
+                        match new {
+                            Ok(new) => ui.label(new),
+                            Err(e) => {
+                                valid = false;
+                                ui.colored_label(Color32::RED, format!("{e:#}"))
+                            }
+                        };
+                        ui.end_row();
+                    }
+                });
+
+                ui.separator();
+                ui.strong("Receipt types");
+
+                ui.horizontal_wrapped(|ui| {
+                    for kind in ReceiptKind::ALL {
+                        let mut enabled = self.settings.receipt_kinds.contains(&kind);
+                        let label = format!("{} {}", kind.icon(), kind.name());
+                        if ui
+                            .checkbox(&mut enabled, label)
+                            .on_hover_text(kind.extensions().join(", "))
+                            .changed()
+                        {
+                            if enabled {
+                                self.settings.receipt_kinds.push(kind);
+                            } else {
+                                self.settings.receipt_kinds.retain(|k| *k != kind);
+                            }
+                        }
+                    }
+                });
+                ui.checkbox(
+                    &mut self.settings.convert_images,
+                    "Convert images to PDF when renaming",
+                );
+
+                ui.separator();
+                ui.strong("Receipt folders");
+
+                let mut remove = None;
+                Grid::new("receipt_folders").striped(true).show(ui, |ui| {
+                    for (idx, folder) in self.settings.receipt_folders.iter_mut().enumerate() {
+                        ui.label(folder.path.to_string_lossy());
+                        ui.checkbox(&mut folder.recursive, "recursive");
+                        if ui.small_button("remove").clicked() {
+                            remove = Some(idx);
+                        }
+                        ui.end_row();
+                    }
+                });
+                if let Some(idx) = remove {
+                    self.settings.receipt_folders.remove(idx);
+                }
+                if ui
+                    .add_enabled(self.wait_for_folder.is_none(), Button::new("Add Folder…"))
+                    .clicked()
+                {
+                    self.pick_folder();
+                }
+                if self.settings.receipt_folders.is_empty() {
+                    ui.weak("no folders: the folder of the csv is searched");
+                }
+
+                ui.label("Ignore patterns (one glob per line, e.g. `*.tmp` or `archive/**`):");
+                ui.add(
+                    TextEdit::multiline(&mut self.ignore_input)
+                        .desired_rows(3)
+                        .desired_width(f32::INFINITY),
+                );
+                let ignore_patterns: Vec<String> = self
+                    .ignore_input
+                    .lines()
+                    .map(str::trim)
+                    .filter(|p| !p.is_empty())
+                    .map(str::to_string)
+                    .collect();
+                if let Err(e) = discovery::ignore_set(&ignore_patterns) {
+                    valid = false;
+                    ui.colored_label(Color32::RED, format!("{e:#}"));
+                }
+
+                ui.separator();
+
+                ui.horizontal(|ui| {
+                    if ui.add_enabled(valid, Button::new("Apply")).clicked() {
+                        self.settings.ignore_patterns = ignore_patterns;
+                        result = Some(self.settings.clone());
+                        self.open = false;
+                    }
+                    if ui.button("Cancel").clicked() {
+                        self.open = false;
+                    }
+                });
+            });
+
+        self.open &= open;
+
+        result
+    }
+}
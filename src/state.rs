@@ -0,0 +1,285 @@
+use anyhow::{bail, Result};
+use std::{collections::BTreeMap, path::PathBuf};
+
+use crate::{
+    discovery::ReceiptFolder,
+    format::FormatKind,
+    receipttype::ReceiptKind,
+    reconcile::Balances,
+    rowmetadata::{AssignedReceipt, RowMetaData},
+    tablelayout::TableLayout,
+    template,
+};
+
+pub const STATE_VERSION: u32 = 2;
+
+#[derive(Debug, Clone, serde::Deserialize, serde::Serialize)]
+pub struct StateData {
+    pub version: u32,
+    pub show_hidden: bool,
+    /// legacy meta data indexed by csv line, migrated into `rows` once the csv is read
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub row_meta_data: Vec<RowMetaData>,
+    /// meta data keyed by transaction fingerprint
+    #[serde(default)]
+    pub rows: BTreeMap<String, RowMetaData>,
+    #[serde(default)]
+    pub format: Option<FormatKind>,
+    #[serde(default)]
+    pub name_template: Option<String>,
+    /// receipt types shown in the file list, all if unset
+    #[serde(default)]
+    pub receipt_kinds: Option<Vec<ReceiptKind>>,
+    /// image receipts are converted to pdf when renamed
+    #[serde(default)]
+    pub convert_images: bool,
+    /// folders searched for receipts, the folder of the csv if empty
+    #[serde(default)]
+    pub receipt_folders: Vec<ReceiptFolder>,
+    #[serde(default)]
+    pub ignore_patterns: Vec<String>,
+    #[serde(default)]
+    pub table_layout: TableLayout,
+    /// statement balances the rows are reconciled against
+    #[serde(default)]
+    pub balances: Balances,
+}
+
+impl StateData {
+    pub fn name_template(&self) -> &str {
+        self.name_template
+            .as_deref()
+            .unwrap_or(template::DEFAULT_TEMPLATE)
+    }
+
+    pub fn receipt_kinds(&self) -> Vec<ReceiptKind> {
+        self.receipt_kinds
+            .clone()
+            .unwrap_or_else(|| ReceiptKind::ALL.to_vec())
+    }
+
+    pub fn receipt_folders(&self) -> Vec<ReceiptFolder> {
+        if self.receipt_folders.is_empty() {
+            return vec![ReceiptFolder {
+                path: PathBuf::new(),
+                recursive: false,
+            }];
+        }
+        self.receipt_folders.clone()
+    }
+}
+
+impl Default for StateData {
+    fn default() -> Self {
+        Self {
+            version: STATE_VERSION,
+            show_hidden: false,
+            row_meta_data: Default::default(),
+            rows: Default::default(),
+            format: Default::default(),
+            name_template: Default::default(),
+            receipt_kinds: Default::default(),
+            convert_images: Default::default(),
+            receipt_folders: Default::default(),
+            ignore_patterns: Default::default(),
+            table_layout: Default::default(),
+            balances: Default::default(),
+        }
+    }
+}
+
+#[derive(Debug, serde::Deserialize)]
+struct VersionProbe {
+    #[serde(default)]
+    version: u32,
+}
+
+/// meta data up to version 1: a single receipt per row
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct RowMetaDataV1 {
+    hidden: bool,
+    receipt: Option<String>,
+    original_name: Option<String>,
+    category: Option<String>,
+}
+
+impl From<RowMetaDataV1> for RowMetaData {
+    fn from(old: RowMetaDataV1) -> Self {
+        Self {
+            hidden: old.hidden,
+            receipts: old
+                .receipt
+                .map(|path| AssignedReceipt {
+                    path,
+                    original_name: old.original_name,
+                    amount: None,
+                })
+                .into_iter()
+                .collect(),
+            category: old.category,
+            no_receipt: None,
+        }
+    }
+}
+
+/// unversioned layout: meta data indexed by csv line and miles and more only
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct StateV0 {
+    show_hidden: bool,
+    row_meta_data: Vec<RowMetaDataV1>,
+    rows: BTreeMap<String, RowMetaDataV1>,
+    format: Option<FormatKind>,
+}
+
+#[derive(Debug, Default, serde::Deserialize)]
+#[serde(default)]
+struct StateV1 {
+    show_hidden: bool,
+    row_meta_data: Vec<RowMetaDataV1>,
+    rows: BTreeMap<String, RowMetaDataV1>,
+    format: Option<FormatKind>,
+    name_template: Option<String>,
+    receipt_kinds: Option<Vec<ReceiptKind>>,
+    convert_images: bool,
+    receipt_folders: Vec<ReceiptFolder>,
+    ignore_patterns: Vec<String>,
+}
+
+fn migrate_v0(old: StateV0) -> StateV1 {
+    StateV1 {
+        show_hidden: old.show_hidden,
+        row_meta_data: old.row_meta_data,
+        rows: old.rows,
+        format: old.format.or(Some(FormatKind::MilesAndMore)),
+        ..Default::default()
+    }
+}
+
+fn migrate_v1(old: StateV1) -> StateData {
+    StateData {
+        version: 2,
+        show_hidden: old.show_hidden,
+        row_meta_data: old.row_meta_data.into_iter().map(Into::into).collect(),
+        rows: old
+            .rows
+            .into_iter()
+            .map(|(id, meta)| (id, meta.into()))
+            .collect(),
+        format: old.format,
+        name_template: old.name_template,
+        receipt_kinds: old.receipt_kinds,
+        convert_images: old.convert_images,
+        receipt_folders: old.receipt_folders,
+        ignore_patterns: old.ignore_patterns,
+        table_layout: Default::default(),
+        balances: Default::default(),
+    }
+}
+
+/// parses a state file of any known version and migrates it to the current layout
+pub fn from_str(content: &str) -> Result<StateData> {
+    let probe: VersionProbe = ron::from_str(content)?;
+
+    let state = match probe.version {
+        0 => migrate_v1(migrate_v0(ron::from_str(content)?)),
+        1 => migrate_v1(ron::from_str(content)?),
+        STATE_VERSION => ron::from_str(content)?,
+        v => bail!(
+            "state version {} is newer than supported ({})",
+            v,
+            STATE_VERSION
+        ),
+    };
+
+    if probe.version != STATE_VERSION {
+        tracing::info!(
+            "migrated state from version {} to {}",
+            probe.version,
+            STATE_VERSION
+        );
+    }
+
+    Ok(state)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn migrate_unversioned() {
+        let state = from_str(
+            r#"(
+                show_hidden: true,
+                row_meta_data: [(hidden: true, receipt: Some("a.pdf"), original_name: Some("scan.pdf"))],
+                rows: {"x": (receipt: Some("b.pdf"), category: Some("travel"))},
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(state.version, STATE_VERSION);
+        assert!(state.show_hidden);
+        assert_eq!(state.format, Some(FormatKind::MilesAndMore));
+
+        let meta = &state.row_meta_data[0];
+        assert!(meta.hidden);
+        assert_eq!(meta.receipts.len(), 1);
+        assert_eq!(meta.receipts[0].path, "a.pdf");
+        assert_eq!(meta.receipts[0].original_name.as_deref(), Some("scan.pdf"));
+
+        let meta = &state.rows["x"];
+        assert_eq!(meta.receipts[0].path, "b.pdf");
+        assert_eq!(meta.category.as_deref(), Some("travel"));
+    }
+
+    #[test]
+    fn migrate_v1() {
+        let state = from_str(
+            r#"(
+                version: 1,
+                format: Some(Dkb),
+                name_template: Some("{index}"),
+                convert_images: true,
+                ignore_patterns: ["*.tmp"],
+                rows: {
+                    "x": (receipt: Some("a.pdf")),
+                    "y": (hidden: true),
+                },
+            )"#,
+        )
+        .unwrap();
+
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.format, Some(FormatKind::Dkb));
+        assert_eq!(state.name_template(), "{index}");
+        assert!(state.convert_images);
+        assert_eq!(state.ignore_patterns, vec!["*.tmp".to_string()]);
+        assert_eq!(state.rows["x"].receipts[0].path, "a.pdf");
+        assert!(state.rows["y"].hidden);
+        assert!(!state.rows["y"].has_receipts());
+    }
+
+    #[test]
+    fn current_version() {
+        let mut state = StateData::default();
+        state
+            .rows
+            .entry("x".to_string())
+            .or_default()
+            .assign("a.pdf".to_string());
+
+        let state = from_str(&ron::to_string(&state).unwrap()).unwrap();
+        assert_eq!(state.version, STATE_VERSION);
+        assert_eq!(state.rows["x"].receipts[0].path, "a.pdf");
+    }
+
+    #[test]
+    fn newer_version() {
+        let e = from_str(&format!("(version: {})", STATE_VERSION + 1)).unwrap_err();
+        assert!(e.to_string().contains("newer"));
+
+        assert!(from_str("(version: ").is_err());
+    }
+}
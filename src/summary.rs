@@ -0,0 +1,198 @@
+use crate::{
+    rowmetadata::RowMetaData,
+    transaction::{Amount, Transaction},
+};
+
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Totals {
+    pub count: usize,
+    pub amount: Amount,
+}
+
+impl Totals {
+    fn add(&mut self, amount: Amount) {
+        self.count += 1;
+        self.amount = self.amount + amount;
+    }
+}
+
+/// progress of a statement, hidden rows need no receipt
+#[derive(Debug, Clone, Copy, Default)]
+pub struct Summary {
+    pub total: Totals,
+    pub assigned: Totals,
+    pub unassigned: Totals,
+    pub hidden: Totals,
+    /// rows marked as needing no receipt
+    pub no_receipt: Totals,
+    /// charges of unassigned rows, refunds are left out
+    pub missing_charges: Amount,
+    /// rows without a valid amount, not part of the totals
+    pub unparsed: usize,
+}
+
+impl Summary {
+    /// `charges_positive` is the sign of charges in the column mapping of the statement
+    pub fn new<'a>(
+        rows: impl Iterator<Item = (Option<&'a Transaction>, Option<&'a RowMetaData>)>,
+        charges_positive: bool,
+    ) -> Self {
+        let mut res = Self::default();
+        let mut unassigned = Vec::new();
+
+        for (transaction, meta) in rows {
+            let Some(transaction) = transaction else {
+                res.unparsed += 1;
+                continue;
+            };
+            let amount = transaction.amount;
+
+            res.total.add(amount);
+            match meta {
+                Some(meta) if meta.hidden => res.hidden.add(amount),
+                Some(meta) if meta.has_receipts() => res.assigned.add(amount),
+                Some(meta) if meta.no_receipt.is_some() => res.no_receipt.add(amount),
+                _ => {
+                    res.unassigned.add(amount);
+                    unassigned.push(amount);
+                }
+            }
+        }
+
+        res.missing_charges = unassigned
+            .into_iter()
+            .filter(|amount| {
+                if charges_positive {
+                    *amount > Amount::ZERO
+                } else {
+                    *amount < Amount::ZERO
+                }
+            })
+            .map(Amount::abs)
+            .sum();
+
+        res
+    }
+
+    /// share of rows that are assigned, hidden or need no receipt
+    pub fn progress(&self) -> f32 {
+        if self.total.count == 0 {
+            return 1.0;
+        }
+        (self.assigned.count + self.hidden.count + self.no_receipt.count) as f32
+            / self.total.count as f32
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn transaction(amount: &str) -> Transaction {
+        Transaction {
+            date: chrono::NaiveDate::from_ymd_opt(2023, 1, 1).unwrap(),
+            amount: Amount::parse(amount).unwrap(),
+            currency: "EUR".to_string(),
+            description: "Shop".to_string(),
+        }
+    }
+
+    fn meta(edit: impl FnOnce(&mut RowMetaData)) -> Option<RowMetaData> {
+        let mut meta = RowMetaData::default();
+        edit(&mut meta);
+        Some(meta)
+    }
+
+    fn summary(
+        rows: &[(Option<Transaction>, Option<RowMetaData>)],
+        charges_positive: bool,
+    ) -> Summary {
+        Summary::new(
+            rows.iter().map(|(t, meta)| (t.as_ref(), meta.as_ref())),
+            charges_positive,
+        )
+    }
+
+    fn amount(input: &str) -> Amount {
+        Amount::parse(input).unwrap()
+    }
+
+    #[test]
+    fn totals() {
+        let rows = vec![
+            (
+                Some(transaction("-10")),
+                meta(|m| m.assign("a.pdf".to_string())),
+            ),
+            (Some(transaction("-20")), meta(|m| m.hidden = true)),
+            (
+                Some(transaction("-1,50")),
+                meta(|m| m.no_receipt = Some("fee".to_string())),
+            ),
+            // hidden wins over an assigned receipt
+            (
+                Some(transaction("-5")),
+                meta(|m| {
+                    m.hidden = true;
+                    m.assign("b.pdf".to_string());
+                }),
+            ),
+            (Some(transaction("-30")), None),
+            (Some(transaction("7")), meta(|_| {})),
+            (None, None),
+        ];
+        let summary = summary(&rows, false);
+
+        assert_eq!(summary.total.count, 6);
+        assert_eq!(summary.total.amount, amount("-59,50"));
+        assert_eq!(
+            (summary.assigned.count, summary.assigned.amount),
+            (1, amount("-10"))
+        );
+        assert_eq!(
+            (summary.hidden.count, summary.hidden.amount),
+            (2, amount("-25"))
+        );
+        assert_eq!(
+            (summary.no_receipt.count, summary.no_receipt.amount),
+            (1, amount("-1,50"))
+        );
+        assert_eq!(
+            (summary.unassigned.count, summary.unassigned.amount),
+            (2, amount("-23"))
+        );
+        assert_eq!(summary.unparsed, 1);
+        assert_eq!(summary.progress(), 4.0 / 6.0);
+    }
+
+    #[test]
+    fn missing_charges() {
+        // mostly refunds, the sign still comes from the mapping
+        let rows = vec![
+            (Some(transaction("-10")), None),
+            (Some(transaction("4")), None),
+            (Some(transaction("7")), None),
+        ];
+        assert_eq!(summary(&rows, false).missing_charges, amount("10"));
+        assert_eq!(summary(&rows, true).missing_charges, amount("11"));
+
+        // as many refunds as charges
+        let rows = vec![
+            (Some(transaction("-10")), None),
+            (Some(transaction("3")), None),
+        ];
+        assert_eq!(summary(&rows, false).missing_charges, amount("10"));
+        assert_eq!(summary(&rows, true).missing_charges, amount("3"));
+    }
+
+    #[test]
+    fn progress() {
+        assert_eq!(Summary::default().progress(), 1.0);
+
+        let rows = vec![
+            (Some(transaction("-10")), None),
+            (Some(transaction("-10")), meta(|m| m.hidden = true)),
+        ];
+        assert_eq!(summary(&rows, false).progress(), 0.5);
+    }
+}
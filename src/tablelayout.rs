@@ -0,0 +1,151 @@
+use std::collections::BTreeMap;
+
+/// order, visibility and widths of the csv columns in the table
+#[derive(Debug, Clone, Default, PartialEq, serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+pub struct TableLayout {
+    /// csv column indices in display order, columns missing here are appended
+    pub order: Vec<usize>,
+    pub hidden: Vec<usize>,
+    pub widths: BTreeMap<usize, f32>,
+}
+
+impl TableLayout {
+    /// all columns in display order, hidden ones included
+    pub fn ordered(&self, columns: usize) -> Vec<usize> {
+        let mut res: Vec<usize> = self
+            .order
+            .iter()
+            .copied()
+            .filter(|c| *c < columns)
+            .collect();
+        res.extend((0..columns).filter(|c| !self.order.contains(c)));
+        res
+    }
+
+    pub fn visible(&self, columns: usize) -> Vec<usize> {
+        self.ordered(columns)
+            .into_iter()
+            .filter(|c| !self.is_hidden(*c))
+            .collect()
+    }
+
+    pub fn is_hidden(&self, column: usize) -> bool {
+        self.hidden.contains(&column)
+    }
+
+    pub fn set_hidden(&mut self, column: usize, hidden: bool) {
+        self.hidden.retain(|c| *c != column);
+        if hidden {
+            self.hidden.push(column);
+        }
+    }
+
+    /// moves the column by `offset` positions among the visible columns
+    pub fn move_column(&mut self, column: usize, offset: isize, columns: usize) {
+        let visible = self.visible(columns);
+        let Some(pos) = visible.iter().position(|c| *c == column) else {
+            return;
+        };
+        let Some(neighbour) = pos
+            .checked_add_signed(offset)
+            .and_then(|pos| visible.get(pos))
+        else {
+            return;
+        };
+
+        let mut order = self.ordered(columns);
+        let a = order.iter().position(|c| *c == column);
+        let b = order.iter().position(|c| c == neighbour);
+        if let (Some(a), Some(b)) = (a, b) {
+            order.swap(a, b);
+            self.order = order;
+        }
+    }
+
+    /// forgets columns the csv does not have, e.g. after it was replaced by a shorter one
+    pub fn retain_columns(&mut self, columns: usize) {
+        self.order.retain(|c| *c < columns);
+        self.hidden.retain(|c| *c < columns);
+        self.widths.retain(|c, _| *c < columns);
+    }
+
+    pub fn width(&self, column: usize) -> Option<f32> {
+        self.widths.get(&column).copied()
+    }
+
+    pub fn set_width(&mut self, column: usize, width: f32) {
+        // ignore rounding noise so the state does not change every frame
+        if !self.width(column).is_some_and(|w| (w - width).abs() < 0.5) {
+            self.widths.insert(column, width);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn order_and_visibility() {
+        let mut layout = TableLayout::default();
+        assert_eq!(layout.ordered(3), vec![0, 1, 2]);
+
+        layout.order = vec![2, 0];
+        layout.set_hidden(0, true);
+        assert_eq!(layout.ordered(3), vec![2, 0, 1]);
+        assert_eq!(layout.visible(3), vec![2, 1]);
+
+        layout.set_hidden(0, false);
+        assert_eq!(layout.visible(3), vec![2, 0, 1]);
+    }
+
+    #[test]
+    fn move_across_hidden() {
+        let mut layout = TableLayout::default();
+        layout.set_hidden(1, true);
+
+        // `1` is skipped, `2` swaps places with the visible neighbour `0`
+        layout.move_column(2, -1, 4);
+        assert_eq!(layout.ordered(4), vec![2, 1, 0, 3]);
+        assert_eq!(layout.visible(4), vec![2, 0, 3]);
+
+        layout.move_column(0, 1, 4);
+        assert_eq!(layout.visible(4), vec![2, 3, 0]);
+
+        // nothing to swap with at the edges or for hidden columns
+        layout.move_column(2, -1, 4);
+        layout.move_column(0, 1, 4);
+        layout.move_column(1, 1, 4);
+        assert_eq!(layout.ordered(4), vec![2, 1, 3, 0]);
+    }
+
+    #[test]
+    fn fewer_columns() {
+        let mut layout = TableLayout {
+            order: vec![4, 3, 1, 0, 2],
+            hidden: vec![3, 0],
+            widths: BTreeMap::from([(1, 80.0), (4, 120.0)]),
+        };
+        assert_eq!(layout.ordered(2), vec![1, 0]);
+        assert_eq!(layout.visible(2), vec![1]);
+
+        layout.retain_columns(2);
+        assert_eq!(layout.order, vec![1, 0]);
+        assert_eq!(layout.hidden, vec![0]);
+        assert_eq!(layout.widths, BTreeMap::from([(1, 80.0)]));
+    }
+
+    #[test]
+    fn width_noise() {
+        let mut layout = TableLayout::default();
+        assert_eq!(layout.width(0), None);
+
+        layout.set_width(0, 100.0);
+        layout.set_width(0, 100.4);
+        assert_eq!(layout.width(0), Some(100.0));
+
+        layout.set_width(0, 101.0);
+        assert_eq!(layout.width(0), Some(101.0));
+    }
+}
@@ -0,0 +1,177 @@
+use anyhow::{bail, Result};
+use std::fmt::Write;
+
+use crate::transaction::Transaction;
+
+pub const DEFAULT_TEMPLATE: &str = "{index}-{date:%d.%m.%Y}{amount}{currency}-{description}";
+const DEFAULT_DATE_FORMAT: &str = "%Y-%m-%d";
+const MAX_FILE_NAME_LEN: usize = 200;
+
+pub const PLACEHOLDERS: &[(&str, &str)] = &[
+    ("{index}", "row number, zero padded to 3 digits"),
+    ("{date:%Y-%m-%d}", "booking date, strftime format"),
+    ("{amount}", "amount with sign"),
+    ("{currency}", "currency of the amount"),
+    ("{description}", "description of the transaction"),
+    ("{category}", "category assigned to the row"),
+    ("{original}", "original file name without extension"),
+];
+
+pub struct TemplateContext<'a> {
+    pub index: usize,
+    pub transaction: &'a Transaction,
+    pub category: &'a str,
+    pub original: &'a str,
+}
+
+/// renders the file name (without extension) for a transaction
+pub fn render(template: &str, ctx: &TemplateContext<'_>) -> Result<String> {
+    let res = sanitize(&render_raw(template, ctx)?);
+    if res.is_empty() {
+        bail!("template renders an empty name");
+    }
+
+    Ok(res)
+}
+
+/// renders without replacing characters invalid in file names
+pub fn render_raw(template: &str, ctx: &TemplateContext<'_>) -> Result<String> {
+    let mut res = String::new();
+    let mut rest = template;
+
+    while let Some(start) = rest.find('{') {
+        res.push_str(&rest[..start]);
+        let Some(end) = rest[start..].find('}') else {
+            bail!("unclosed '{{' in template");
+        };
+        let placeholder = &rest[start + 1..start + end];
+        res.push_str(&render_placeholder(placeholder, ctx)?);
+        rest = &rest[start + end + 1..];
+    }
+
+    if rest.contains('}') {
+        bail!("unexpected '}}' in template");
+    }
+    res.push_str(rest);
+
+    Ok(res)
+}
+
+fn render_placeholder(placeholder: &str, ctx: &TemplateContext<'_>) -> Result<String> {
+    let (name, spec) = placeholder
+        .split_once(':')
+        .map_or((placeholder, None), |(name, spec)| (name, Some(spec)));
+
+    Ok(match name {
+        "index" => format!("{:0>3}", ctx.index),
+        "date" => {
+            let spec = spec.unwrap_or(DEFAULT_DATE_FORMAT);
+            // `to_string` panics on specifiers a date cannot fill in, like `%H` or `%z`
+            let mut date = String::new();
+            if write!(date, "{}", ctx.transaction.date.format(spec)).is_err() {
+                bail!("invalid date format: '{}'", spec);
+            }
+            date
+        }
+        "amount" => ctx.transaction.amount.to_string(),
+        "currency" => ctx.transaction.currency.clone(),
+        "description" => ctx.transaction.description.clone(),
+        "category" => ctx.category.to_string(),
+        "original" => ctx.original.to_string(),
+        _ => bail!("unknown placeholder: '{{{}}}'", placeholder),
+    })
+}
+
+fn is_invalid(c: char) -> bool {
+    matches!(c, '/' | '\\' | ':' | '*' | '?' | '"' | '<' | '>' | '|') || c.is_control()
+}
+
+/// characters that `sanitize` replaces
+pub fn invalid_chars(name: &str) -> Vec<char> {
+    let mut res: Vec<char> = name.chars().filter(|c| is_invalid(*c)).collect();
+    res.sort_unstable();
+    res.dedup();
+    res
+}
+
+/// replaces characters that are invalid in file names on any of the supported platforms
+pub fn sanitize(name: &str) -> String {
+    let mut res: String = name
+        .chars()
+        .map(|c| if is_invalid(c) { '_' } else { c })
+        .collect();
+
+    while res.len() > MAX_FILE_NAME_LEN {
+        res.pop();
+    }
+
+    res.trim_matches(|c: char| c == '.' || c.is_whitespace())
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::transaction::Amount;
+    use chrono::NaiveDate;
+
+    fn transaction() -> Transaction {
+        Transaction {
+            date: NaiveDate::from_ymd_opt(2023, 1, 31).unwrap(),
+            amount: Amount::parse("-12,34").unwrap(),
+            currency: "EUR".to_string(),
+            description: "Hotel: Berlin/Mitte".to_string(),
+        }
+    }
+
+    fn render_with(template: &str) -> Result<String> {
+        let transaction = transaction();
+        render(
+            template,
+            &TemplateContext {
+                index: 7,
+                transaction: &transaction,
+                category: "travel",
+                original: "scan 01",
+            },
+        )
+    }
+
+    #[test]
+    fn placeholders() {
+        assert_eq!(
+            render_with(DEFAULT_TEMPLATE).unwrap(),
+            "007-31.01.2023-12.34EUR-Hotel_ Berlin_Mitte"
+        );
+        assert_eq!(render_with("{date}").unwrap(), "2023-01-31");
+        assert_eq!(render_with("{date:%Y%m}").unwrap(), "202301");
+        assert_eq!(
+            render_with("{category}_{original}_{amount}").unwrap(),
+            "travel_scan 01_-12.34"
+        );
+        assert_eq!(render_with("receipt").unwrap(), "receipt");
+    }
+
+    #[test]
+    fn invalid_templates() {
+        for template in ["{unknown}", "{index", "index}", "", "..."] {
+            assert!(render_with(template).is_err(), "{template}");
+        }
+    }
+
+    #[test]
+    fn date_formats_without_date() {
+        // specifiers that need a time or timezone must not panic
+        for spec in ["%H", "%s", "%z", "%Y-%H", "%Q", "%"] {
+            assert!(render_with(&format!("{{date:{spec}}}")).is_err(), "{spec}");
+        }
+    }
+
+    #[test]
+    fn sanitize_names() {
+        assert_eq!(sanitize("a/b\\c:d*e?f\"g<h>i|j"), "a_b_c_d_e_f_g_h_i_j");
+        assert_eq!(sanitize(" .name. "), "name");
+        assert_eq!(sanitize(&"x".repeat(300)).len(), MAX_FILE_NAME_LEN);
+        assert_eq!(invalid_chars("a:b/c:"), vec!['/', ':']);
+    }
+}
@@ -0,0 +1,97 @@
+use egui::{ColorImage, TextureHandle, TextureOptions};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::mpsc::{channel, Receiver},
+};
+
+use crate::receipttype::ReceiptKind;
+
+/// longest side of a thumbnail in pixels
+const THUMBNAIL_SIZE: u32 = 64;
+
+/// loads thumbnails of image receipts in the background
+#[derive(Default)]
+pub struct Thumbnails {
+    /// `None` if the image could not be decoded
+    textures: HashMap<PathBuf, Option<TextureHandle>>,
+    pending: Option<Receiver<(PathBuf, Option<ColorImage>)>>,
+}
+
+impl std::fmt::Debug for Thumbnails {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("Thumbnails")
+            .field("textures", &self.textures.len())
+            .finish()
+    }
+}
+
+impl Thumbnails {
+    /// starts loading the thumbnails of all image receipts not seen before
+    pub fn load(&mut self, receipts: &[PathBuf]) {
+        let todo: Vec<PathBuf> = receipts
+            .iter()
+            .filter(|p| ReceiptKind::from_path(p) == Some(ReceiptKind::Image))
+            .filter(|p| !self.textures.contains_key(*p))
+            .cloned()
+            .collect();
+
+        if todo.is_empty() || self.pending.is_some() {
+            return;
+        }
+
+        let (tx, rx) = channel();
+        std::thread::spawn(move || {
+            for path in todo {
+                let image = thumbnail(&path)
+                    .map_err(|e| tracing::warn!("no thumbnail for {:?}: {}", path, e))
+                    .ok();
+                if tx.send((path, image)).is_err() {
+                    break;
+                }
+            }
+        });
+
+        self.pending = Some(rx);
+    }
+
+    /// uploads finished thumbnails, returns true if a batch finished
+    pub fn poll(&mut self, ctx: &egui::Context) -> bool {
+        let Some(rx) = self.pending.as_ref() else {
+            return false;
+        };
+
+        loop {
+            match rx.try_recv() {
+                Ok((path, image)) => {
+                    let texture = image.map(|image| {
+                        ctx.load_texture(path.to_string_lossy(), image, TextureOptions::default())
+                    });
+                    self.textures.insert(path, texture);
+                }
+                Err(std::sync::mpsc::TryRecvError::Empty) => return false,
+                Err(std::sync::mpsc::TryRecvError::Disconnected) => {
+                    self.pending = None;
+                    return true;
+                }
+            }
+        }
+    }
+
+    pub fn is_busy(&self) -> bool {
+        self.pending.is_some()
+    }
+
+    pub fn get(&self, path: &Path) -> Option<&TextureHandle> {
+        self.textures.get(path).and_then(Option::as_ref)
+    }
+}
+
+fn thumbnail(path: &Path) -> image::ImageResult<ColorImage> {
+    let image = image::open(path)?
+        .thumbnail(THUMBNAIL_SIZE, THUMBNAIL_SIZE)
+        .to_rgba8();
+    let size = [image.width() as usize, image.height() as usize];
+
+    Ok(ColorImage::from_rgba_unmultiplied(size, image.as_raw()))
+}
@@ -0,0 +1,414 @@
+use anyhow::{bail, Context, Result};
+use chrono::NaiveDate;
+use std::{
+    fmt,
+    iter::Sum,
+    ops::{Add, Neg, Sub},
+};
+
+use crate::{
+    format::{read_date, ColumnMapping, StatementFormat},
+    project::CsvRow,
+};
+
+/// fixed point amount in hundredths of the currency unit
+#[derive(
+    Debug,
+    Clone,
+    Copy,
+    Default,
+    PartialEq,
+    Eq,
+    PartialOrd,
+    Ord,
+    Hash,
+    serde::Deserialize,
+    serde::Serialize,
+)]
+pub struct Amount(i64);
+
+impl Amount {
+    pub const ZERO: Amount = Amount(0);
+
+    pub fn abs(self) -> Self {
+        Self(self.0.abs())
+    }
+
+    /// accepts `1.234,56`, `1,234.56`, `-12,3`, `12,34-`, `(12.34)`, `-€12`, optional currency
+    /// symbols and the `S`/`H` (soll/haben) debit and credit markers of german statements
+    pub fn parse(input: &str) -> Result<Self> {
+        let (input_amount, mut negative) = debit_credit_marker(input);
+
+        let trim_currency = |s: &str| {
+            s.trim_matches(|c: char| c.is_alphabetic() || matches!(c, '€' | '$' | '£'))
+                .to_string()
+        };
+
+        let mut s: String = input_amount
+            .chars()
+            .filter(|c| !c.is_whitespace() && *c != '\'')
+            .collect();
+        s = trim_currency(&s);
+
+        if s.starts_with('(') && s.ends_with(')') {
+            negative = !negative;
+            s = s[1..s.len() - 1].to_string();
+        }
+        if let Some(rest) = s.strip_prefix('-') {
+            negative = !negative;
+            s = rest.to_string();
+        } else if let Some(rest) = s.strip_suffix('-') {
+            negative = !negative;
+            s = rest.to_string();
+        } else if let Some(rest) = s.strip_prefix('+') {
+            s = rest.to_string();
+        }
+        // the symbol may sit between the sign and the digits, e.g. `-€12`
+        s = trim_currency(&s);
+
+        if s.is_empty() {
+            bail!("empty amount");
+        }
+
+        let has_both = s.contains('.') && s.contains(',');
+        let (int, frac, decimal) = match s.rfind(['.', ',']) {
+            Some(pos) if has_both || s.len() - pos - 1 <= 2 => {
+                (&s[..pos], &s[pos + 1..], s[pos..].chars().next())
+            }
+            _ => (s.as_str(), "", None),
+        };
+
+        // thousands separators need full groups of three, `0,001` or `12.3.4` are no amounts
+        let mut groups = int.split(['.', ',']);
+        let first = groups.next().unwrap_or_default();
+        if first.len() < int.len()
+            && (first.is_empty()
+                || first.len() > 3
+                || first.starts_with('0')
+                || groups.any(|g| g.len() != 3)
+                || (int.contains('.') && int.contains(','))
+                || decimal.is_some_and(|d| int.contains(d)))
+        {
+            bail!("invalid digit grouping: '{}'", input);
+        }
+
+        let int: String = int.chars().filter(|c| *c != '.' && *c != ',').collect();
+
+        if !int.chars().all(|c| c.is_ascii_digit()) || !frac.chars().all(|c| c.is_ascii_digit()) {
+            bail!("invalid amount: '{}'", input);
+        }
+        if frac.len() > 2 {
+            bail!("too many decimals: '{}'", input);
+        }
+
+        let units: i64 = if int.is_empty() {
+            0
+        } else {
+            int.parse()
+                .with_context(|| format!("invalid amount: '{input}'"))?
+        };
+        let frac: i64 = format!("{frac:0<2}").parse().unwrap_or_default();
+
+        let cents = units
+            .checked_mul(100)
+            .and_then(|c| c.checked_add(frac))
+            .ok_or_else(|| anyhow::anyhow!("amount too large: '{}'", input))?;
+
+        Ok(Self(if negative { -cents } else { cents }))
+    }
+}
+
+/// splits off a trailing `S` (debit) or `H` (credit), returns whether the amount is a debit
+fn debit_credit_marker(input: &str) -> (&str, bool) {
+    let input = input.trim();
+    for (marker, negative) in [('S', true), ('H', false)] {
+        if let Some(rest) = input.strip_suffix(marker) {
+            // `12,34 S` and `12,34S`, but not currency codes like `UAH`
+            if rest.ends_with(|c: char| c.is_whitespace() || c.is_ascii_digit()) {
+                return (rest, negative);
+            }
+        }
+    }
+    (input, false)
+}
+
+impl fmt::Display for Amount {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let sign = if self.0 < 0 { "-" } else { "" };
+        let abs = self.0.unsigned_abs();
+        write!(f, "{}{}.{:02}", sign, abs / 100, abs % 100)
+    }
+}
+
+impl Add for Amount {
+    type Output = Self;
+
+    fn add(self, rhs: Self) -> Self {
+        Self(self.0 + rhs.0)
+    }
+}
+
+impl Sub for Amount {
+    type Output = Self;
+
+    fn sub(self, rhs: Self) -> Self {
+        Self(self.0 - rhs.0)
+    }
+}
+
+impl Neg for Amount {
+    type Output = Self;
+
+    fn neg(self) -> Self {
+        Self(-self.0)
+    }
+}
+
+impl Sum for Amount {
+    fn sum<I: Iterator<Item = Self>>(iter: I) -> Self {
+        iter.fold(Amount::ZERO, Add::add)
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Transaction {
+    pub date: NaiveDate,
+    pub amount: Amount,
+    pub currency: String,
+    pub description: String,
+}
+
+impl Transaction {
+    pub fn from_row(
+        row: &CsvRow,
+        columns: &ColumnMapping,
+        format: &dyn StatementFormat,
+    ) -> Result<Self> {
+        let date_column = columns.booking_date.or(columns.value_date);
+        let date = parse_date(row.cell(date_column), format.date_formats())?;
+
+        if columns.amount.is_none() {
+            bail!("no amount column mapped");
+        }
+        let amount = Amount::parse(row.cell(columns.amount))?;
+
+        let currency = match row.cell(columns.currency).trim() {
+            "" => format.currency(),
+            currency => currency,
+        }
+        .to_string();
+
+        Ok(Self {
+            date,
+            amount,
+            currency,
+            description: row.cell(columns.description).trim().to_string(),
+        })
+    }
+}
+
+/// identifies a transaction independent of its position in the statement
+pub fn fingerprint(row: &CsvRow, transaction: Option<&Transaction>) -> String {
+    match transaction {
+        Some(t) => format!(
+            "{}|{}|{}",
+            t.date.format("%Y-%m-%d"),
+            t.amount,
+            t.description
+        ),
+        None => row
+            .cells
+            .iter()
+            .map(|c| c.trim())
+            .collect::<Vec<_>>()
+            .join("|"),
+    }
+}
+
+fn parse_date(input: &str, formats: &[&str]) -> Result<NaiveDate> {
+    let input = input.trim();
+    if input.is_empty() {
+        bail!("empty date");
+    }
+
+    formats
+        .iter()
+        .find_map(|f| read_date(input, f))
+        .ok_or_else(|| anyhow::anyhow!("invalid date: '{}'", input))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::format::{Amex, MilesAndMore};
+
+    fn amount(input: &str) -> i64 {
+        Amount::parse(input).unwrap().0
+    }
+
+    #[test]
+    fn parse_separators() {
+        assert_eq!(amount("12,34"), 1234);
+        assert_eq!(amount("12.34"), 1234);
+        assert_eq!(amount("1.234,56"), 123456);
+        assert_eq!(amount("1,234.56"), 123456);
+        assert_eq!(amount("1'234.56"), 123456);
+        assert_eq!(amount("1.234"), 123400);
+        assert_eq!(amount("1.234.567,89"), 123456789);
+        assert_eq!(amount("0,01"), 1);
+        assert_eq!(amount("12,3"), 1230);
+        assert_eq!(amount("12"), 1200);
+        assert_eq!(amount(",5"), 50);
+    }
+
+    #[test]
+    fn parse_signs() {
+        assert_eq!(amount("-12,34"), -1234);
+        assert_eq!(amount("+12,34"), 1234);
+        assert_eq!(amount("12,34-"), -1234);
+        assert_eq!(amount("(12.34)"), -1234);
+        assert_eq!(amount("- 12,34"), -1234);
+    }
+
+    #[test]
+    fn parse_currency() {
+        assert_eq!(amount("12,34 €"), 1234);
+        assert_eq!(amount("€12.34"), 1234);
+        assert_eq!(amount("-€12"), -1200);
+        assert_eq!(amount("€-12"), -1200);
+        assert_eq!(amount("12,34- EUR"), -1234);
+        assert_eq!(amount("$ (12.34)"), -1234);
+        assert_eq!(amount("12,34 UAH"), 1234);
+    }
+
+    #[test]
+    fn parse_debit_credit_marker() {
+        assert_eq!(amount("12,34 S"), -1234);
+        assert_eq!(amount("12,34S"), -1234);
+        assert_eq!(amount("12,34 H"), 1234);
+        assert_eq!(amount("1.234,56 EUR S"), -123456);
+    }
+
+    #[test]
+    fn parse_invalid() {
+        assert!(Amount::parse("").is_err());
+        assert!(Amount::parse("EUR").is_err());
+        assert!(Amount::parse("12,345").is_ok());
+        assert!(Amount::parse("1.2.3,456").is_err());
+        assert!(Amount::parse("0,001").is_err());
+        assert!(Amount::parse("12.3.4").is_err());
+        assert!(Amount::parse("1,23,456.00").is_err());
+        assert!(Amount::parse("1.234,567.89").is_err());
+        assert!(Amount::parse("1,234,56").is_err());
+        assert!(Amount::parse("1234.567,00").is_err());
+        assert!(Amount::parse("abc 12x3").is_err());
+        assert!(Amount::parse("99999999999999999999").is_err());
+    }
+
+    #[test]
+    fn display() {
+        assert_eq!(Amount(1234).to_string(), "12.34");
+        assert_eq!(Amount(-5).to_string(), "-0.05");
+        assert_eq!(Amount::ZERO.to_string(), "0.00");
+    }
+
+    fn row(cells: &[&str]) -> CsvRow {
+        CsvRow {
+            cells: cells.iter().map(|c| c.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn from_row() {
+        let t = Transaction::from_row(
+            &row(&["03.01.2023", "04.01.2023", " Coffee ", "-3,50", "USD"]),
+            &MilesAndMore.columns(),
+            &MilesAndMore,
+        )
+        .unwrap();
+        assert_eq!(t.date, NaiveDate::from_ymd_opt(2023, 1, 3).unwrap());
+        assert_eq!(t.amount, Amount(-350));
+        assert_eq!(t.currency, "USD");
+        assert_eq!(t.description, "Coffee");
+    }
+
+    #[test]
+    fn two_digit_years() {
+        let formats = crate::format::DATE_FORMATS;
+        let date = |input| parse_date(input, formats).unwrap();
+        assert_eq!(
+            date("03.01.23"),
+            NaiveDate::from_ymd_opt(2023, 1, 3).unwrap()
+        );
+        assert_eq!(
+            date("03.01.2023"),
+            NaiveDate::from_ymd_opt(2023, 1, 3).unwrap()
+        );
+        assert_eq!(
+            date("31.12.99"),
+            NaiveDate::from_ymd_opt(1999, 12, 31).unwrap()
+        );
+
+        // `%d/%m/%Y` has no two digit counterpart
+        assert!(parse_date("31/12/22", formats).is_err());
+
+        let t = Transaction::from_row(
+            &row(&["03.01.23", "", "Coffee", "-3,50", ""]),
+            &MilesAndMore.columns(),
+            &MilesAndMore,
+        )
+        .unwrap();
+        assert_eq!(t.date, NaiveDate::from_ymd_opt(2023, 1, 3).unwrap());
+    }
+
+    #[test]
+    fn from_row_defaults() {
+        // amex has no currency column and its own date format
+        let t = Transaction::from_row(
+            &row(&["31/12/2022", "Hotel", "120,00"]),
+            &Amex.columns(),
+            &Amex,
+        )
+        .unwrap();
+        assert_eq!(t.date, NaiveDate::from_ymd_opt(2022, 12, 31).unwrap());
+        assert_eq!(t.currency, "EUR");
+
+        // the value date is used when there is no booking date
+        let columns = ColumnMapping {
+            booking_date: None,
+            ..MilesAndMore.columns()
+        };
+        let t = Transaction::from_row(
+            &row(&["", "05.01.2023", "Taxi", "12,00 S", ""]),
+            &columns,
+            &MilesAndMore,
+        )
+        .unwrap();
+        assert_eq!(t.date, NaiveDate::from_ymd_opt(2023, 1, 5).unwrap());
+        assert_eq!(t.amount, Amount(-1200));
+        assert_eq!(t.currency, "EUR");
+    }
+
+    #[test]
+    fn from_row_errors() {
+        let columns = MilesAndMore.columns();
+        for cells in [
+            ["", "", "Coffee", "3,50", ""],
+            ["2023-13-45", "", "Coffee", "3,50", ""],
+            ["03.01.2023", "", "Coffee", "", ""],
+            ["03.01.2023", "", "Coffee", "abc", ""],
+        ] {
+            assert!(Transaction::from_row(&row(&cells), &columns, &MilesAndMore).is_err());
+        }
+
+        // short rows leave the missing cells empty
+        assert!(Transaction::from_row(&row(&["03.01.2023"]), &columns, &MilesAndMore).is_err());
+
+        let columns = ColumnMapping {
+            amount: None,
+            ..columns
+        };
+        let cells = row(&["03.01.2023", "", "Coffee", "3,50", ""]);
+        assert!(Transaction::from_row(&cells, &columns, &MilesAndMore).is_err());
+    }
+}
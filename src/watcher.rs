@@ -0,0 +1,71 @@
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use std::{
+    collections::BTreeSet,
+    path::PathBuf,
+    sync::mpsc::{channel, Receiver},
+};
+
+use crate::discovery::ReceiptFolder;
+
+/// watches the receipt folders and collects the paths that changed
+pub struct ReceiptWatcher {
+    _watcher: RecommendedWatcher,
+    events: Receiver<notify::Result<Event>>,
+}
+
+impl std::fmt::Debug for ReceiptWatcher {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("ReceiptWatcher").finish()
+    }
+}
+
+impl ReceiptWatcher {
+    /// folders that cannot be watched are returned as errors
+    pub fn new(
+        folders: &[ReceiptFolder],
+        ctx: egui::Context,
+    ) -> notify::Result<(Self, Vec<String>)> {
+        let (tx, rx) = channel();
+
+        let mut watcher = notify::recommended_watcher(move |event| {
+            if tx.send(event).is_ok() {
+                ctx.request_repaint();
+            }
+        })?;
+
+        let mut errors = Vec::new();
+        for folder in folders {
+            let mode = if folder.recursive {
+                RecursiveMode::Recursive
+            } else {
+                RecursiveMode::NonRecursive
+            };
+            if let Err(e) = watcher.watch(&folder.path, mode) {
+                tracing::warn!("watching {:?} failed: {}", folder.path, e);
+                errors.push(format!("watching {} failed: {}", folder.path.display(), e));
+            }
+        }
+
+        Ok((
+            Self {
+                _watcher: watcher,
+                events: rx,
+            },
+            errors,
+        ))
+    }
+
+    /// paths changed since the last call
+    pub fn poll(&self) -> BTreeSet<PathBuf> {
+        let mut res = BTreeSet::new();
+        while let Ok(event) = self.events.try_recv() {
+            match event {
+                // reading receipts must not trigger a refresh
+                Ok(event) if event.kind.is_access() => {}
+                Ok(event) => res.extend(event.paths),
+                Err(e) => tracing::warn!("watch error: {}", e),
+            }
+        }
+        res
+    }
+}